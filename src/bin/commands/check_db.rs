@@ -0,0 +1,30 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use log::info;
+use respdiff::integrity;
+
+use std::io::{self, Write};
+
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct CheckDb {}
+
+impl Executable for CheckDb {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let env = args.env()?;
+        let problems = integrity::check_env(&env)?;
+
+        let mut out = io::stdout().lock();
+        for problem in &problems {
+            writeln!(out, "{}", problem)?;
+        }
+
+        if problems.is_empty() {
+            info!("no problems found");
+            Ok(())
+        } else {
+            Err(anyhow!("{} problems found", problems.len()))
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use respdiff::sumcmp::{Category, CountChange, SummaryComparison};
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::commands::{load_summary, Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct CompareReports {
+    /// JSON datafile from the reference (older) run.
+    #[arg(value_name = "OLD")]
+    old: PathBuf,
+
+    /// JSON datafile from the new run.
+    #[arg(value_name = "NEW")]
+    new: PathBuf,
+
+    /// Output format.
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+fn format_count(count: &CountChange) -> String {
+    format!("{:>8} {:>8} {:>+8}", count.old, count.new, count.diff())
+}
+
+fn format_category(category: &Option<Category>) -> String {
+    match category {
+        Some(cat) => format!("{} {} != {}", cat.field, cat.exp_val, cat.got_val),
+        None => String::from("(none)"),
+    }
+}
+
+fn write_text(out: &mut impl Write, cmp: &SummaryComparison) -> io::Result<()> {
+    writeln!(out, "{:<40} {:>8} {:>8} {:>8}", "", "OLD", "NEW", "DIFF")?;
+    writeln!(
+        out,
+        "{:<40} {}",
+        "upstream unstable",
+        format_count(&cmp.upstream_unstable)
+    )?;
+    writeln!(
+        out,
+        "{:<40} {}",
+        "not reproducible",
+        format_count(&cmp.not_reproducible)
+    )?;
+    writeln!(
+        out,
+        "{:<40} {}",
+        "usable answers",
+        format_count(&cmp.usable_answers)
+    )?;
+
+    writeln!(out, "\nFIELDS")?;
+    for change in &cmp.fields {
+        writeln!(
+            out,
+            "{:<40} {}",
+            change.field.to_string(),
+            format_count(&change.count)
+        )?;
+    }
+
+    writeln!(out, "\nMISMATCHES")?;
+    for change in &cmp.mismatches {
+        let cat = &change.category;
+        writeln!(
+            out,
+            "{:<40} {}  {:?}",
+            format!("{} {} != {}", cat.field, cat.exp_val, cat.got_val),
+            format_count(&change.count),
+            change.kind
+        )?;
+    }
+
+    writeln!(out, "\nQUERIES WITH CHANGED CATEGORY")?;
+    for change in &cmp.queries {
+        writeln!(
+            out,
+            "{:>10}  {} -> {}",
+            change.key,
+            format_category(&change.old),
+            format_category(&change.new)
+        )?;
+    }
+    Ok(())
+}
+
+impl Executable for CompareReports {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let old = load_summary(args, &self.old)?;
+        let new = load_summary(args, &self.new)?;
+        let cmp = SummaryComparison::new(&old, &new);
+
+        let mut out = io::stdout().lock();
+        match self.format {
+            Format::Text => write_text(&mut out, &cmp)?,
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut out, &cmp)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -6,14 +6,19 @@ use lmdb::{Cursor, Transaction};
 use log::error;
 use rayon::prelude::*;
 use respdiff::{
-    database::{self, answersdb, metadb, queriesdb},
-    dataformat::Report,
+    database::{self, answersdb, metadb, queriesdb, EnvInfo},
+    dataformat::{Provenance, Report},
     matcher::{self, Field, FieldMismatches, Mismatch},
+    output::{
+        load_questions,
+        metrics::{self, ServerStats},
+    },
     QKey,
 };
 
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
 use crate::commands::{Executable, Respdiff};
@@ -23,9 +28,21 @@ pub struct DiffAnswers {
     /// Path to JSON datafile.
     #[arg(short, long, value_name = "FILE")]
     datafile: Option<PathBuf>,
+
+    /// Embed decoded questions of disagreeing queries in the datafile.
+    #[arg(long)]
+    embed_questions: bool,
+
+    /// Embed at most N questions per mismatch (all by default).
+    #[arg(long, value_name = "N", requires = "embed_questions")]
+    embed_limit: Option<usize>,
+
+    /// Write run statistics in OpenMetrics text format to a file.
+    #[arg(long, value_name = "FILE")]
+    metrics: Option<PathBuf>,
 }
 
-type IndexPair = (usize, usize);
+pub type IndexPair = (usize, usize);
 
 /// Returns indices which are used to compare responses in list.
 ///
@@ -34,7 +51,7 @@ type IndexPair = (usize, usize);
 /// compare the answer to.
 /// The second returned value is a vector of tuples each with two indicies -- two servers to be
 /// compared for equality between each other.
-fn indices_to_cmp(target: &str, servers: &[String]) -> Result<(IndexPair, Vec<IndexPair>)> {
+pub fn indices_to_cmp(target: &str, servers: &[String]) -> Result<(IndexPair, Vec<IndexPair>)> {
     let i_target = servers
         .iter()
         .position(|x| x == target)
@@ -102,29 +119,27 @@ impl Executable for DiffAnswers {
             std::process::exit(1);
         }
 
-        let datafile = match &self.datafile {
-            Some(path) => path.clone(),
-            None => {
-                let mut path = args.envdir()?;
-                path.push("report.json");
-                path
-            }
-        };
+        let datafile = args.datafile(&self.datafile)?;
 
-        let env = args.env()?;
+        let (env, EnvInfo { format, order }) = args.checked_env(&config)?;
         let qdb = database::open_db(&env, queriesdb::NAME, false)?;
         let adb = database::open_db(&env, answersdb::NAME, false)?;
         let mdb = database::open_db(&env, metadb::NAME, false)?;
         let txn = env.begin_ro_txn()?;
 
-        let response_lists = answersdb::get_response_lists(adb, &txn)?;
+        let response_lists = answersdb::get_response_lists(adb, &txn, format, order.len())?;
+        // map indices of configured servers to their position in stored response lists
         let (i_cmp_target, i_cmps_others) = indices_to_cmp(&config.diff.target, &config.servers)?;
+        let i_cmp_target = (order[i_cmp_target.0], order[i_cmp_target.1]);
+        let i_cmps_others: Vec<IndexPair> = i_cmps_others
+            .iter()
+            .map(|(j, k)| (order[*j], order[*k]))
+            .collect();
 
         // compare other servers to each other and find their differences
         let others_disagreements = response_lists
             .par_iter()
             .filter_map(|response_list| {
-                assert_eq!(response_list.replies.len(), config.servers.len());
                 for (j, k) in &i_cmps_others {
                     let diff = matcher::compare(
                         &response_list.replies[*j],
@@ -162,15 +177,40 @@ impl Executable for DiffAnswers {
         report.start_time = metadb::read_start_time(mdb, &txn)?;
         report.end_time = metadb::read_end_time(mdb, &txn)?;
 
+        let mut provenance = Provenance::new(&config);
+        provenance.format_version = Some(metadb::read_version(mdb, &txn)?);
+        provenance.db_servers = metadb::read_servers(mdb, &txn)?;
+        report.provenance = Some(provenance);
+
         let mut cur = txn.open_ro_cursor(qdb)?;
         report.total_queries = cur.iter().count() as u64;
 
         let mut cur = txn.open_ro_cursor(adb)?;
         report.total_answers = cur.iter().count() as u64;
 
+        if self.embed_questions {
+            let keys = report.sample_queries(self.embed_limit);
+            report.questions = load_questions(qdb, &txn, keys)?;
+        }
+
         let out = File::create(datafile)?;
         serde_json::to_writer(&out, &report)?;
 
+        if let Some(path) = &self.metrics {
+            let mut stats: Vec<_> = config.servers.iter().map(|n| ServerStats::new(n)).collect();
+            for response_list in &response_lists {
+                for (i, j) in order.iter().enumerate() {
+                    stats[i].add(&response_list.replies[*j]);
+                }
+            }
+            let mut out = BufWriter::new(File::create(path)?);
+            metrics::write_totals(&mut out, report.total_queries, report.total_answers)?;
+            metrics::write_disagreements(&mut out, &report)?;
+            metrics::write_servers(&mut out, &mut stats)?;
+            metrics::write_eof(&mut out)?;
+            out.flush()?;
+        }
+
         Ok(())
     }
 }
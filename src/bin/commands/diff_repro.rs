@@ -0,0 +1,109 @@
+extern crate lmdb;
+
+use anyhow::{anyhow, bail, Result};
+use async_std::{net::SocketAddr, task};
+use clap::Args;
+use log::info;
+use respdiff::{
+    config::ServerConfig,
+    database::{self, queriesdb},
+    dataformat::Report,
+    matcher, transceive, ServerResponseList,
+};
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use crate::commands::{diff_answers::indices_to_cmp, Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct DiffRepro {
+    /// Path to JSON datafile.
+    #[arg(short, long, value_name = "FILE")]
+    datafile: Option<PathBuf>,
+
+    /// Run restart script of each server before sending every query.
+    #[arg(long)]
+    restart: bool,
+}
+
+/// Run restart scripts of all servers that have one configured.
+fn restart_servers(servers: &[(&String, &ServerConfig)]) -> Result<()> {
+    for (name, sconf) in servers {
+        if let Some(script) = &sconf.restart_script {
+            let status = process::Command::new(script).status()?;
+            if !status.success() {
+                bail!("restart script for server {} failed: {}", name, status);
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Executable for DiffRepro {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let config = args.config()?;
+        let datafile = args.datafile(&self.datafile)?;
+
+        let mut report = Report::from_file(&datafile)?;
+
+        let servers = config
+            .servers
+            .iter()
+            .map(|name| match config.server_data.get(name) {
+                Some(sconf) => Ok((name, sconf)),
+                None => Err(anyhow!("missing configuration for server {}", name)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let addrs: Vec<_> = servers
+            .iter()
+            .map(|(_, sconf)| SocketAddr::new(sconf.ip, sconf.port))
+            .collect();
+        let timeout = Duration::from_secs_f64(config.sendrecv.timeout);
+        let (i_cmp_target, i_cmps_others) = indices_to_cmp(&config.diff.target, &config.servers)?;
+
+        let env = args.env()?;
+        let qdb = database::open_db(&env, queriesdb::NAME, false)?;
+        let txn = env.begin_ro_txn()?;
+
+        for key in report.target_disagreeing_queries() {
+            // skip queries which have already proven to be unstable or not reproducible
+            if let Some(counter) = report.reprodata.as_ref().and_then(|r| r.get(&key)) {
+                if counter.retries != counter.upstream_stable || counter.retries != counter.verified
+                {
+                    continue;
+                }
+            }
+
+            let query = queriesdb::get_query(qdb, &txn, key)?;
+            if self.restart {
+                restart_servers(&servers)?;
+            }
+            info!("reproducing query {}", key);
+            let responses = task::block_on(transceive::query_servers(&query, &addrs, timeout));
+            let response_list = ServerResponseList::from(responses);
+
+            let others_agree = i_cmps_others.iter().all(|(j, k)| {
+                matcher::compare(
+                    &response_list.replies[*j],
+                    &response_list.replies[*k],
+                    &config.diff.criteria,
+                )
+                .is_empty()
+            });
+            let mismatches = matcher::compare(
+                &response_list.replies[i_cmp_target.0],
+                &response_list.replies[i_cmp_target.1],
+                &config.diff.criteria,
+            );
+            report.add_repro_result(key, others_agree, &mismatches);
+        }
+
+        let out = File::create(datafile)?;
+        serde_json::to_writer(&out, &report)?;
+
+        Ok(())
+    }
+}
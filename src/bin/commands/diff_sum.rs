@@ -0,0 +1,213 @@
+extern crate lmdb;
+
+use anyhow::{anyhow, Result};
+use clap::{Args, ValueEnum};
+use log::warn;
+use respdiff::{
+    config::Config,
+    database::{self, answersdb, queriesdb, EnvInfo},
+    dataformat::{Report, Summary},
+    output::{embedded_samples, html, junit, markdown, text, top_mismatches, QuerySample},
+    sumstat::{Outlier, SummaryStatistics},
+    QKey,
+};
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::commands::{Executable, Respdiff};
+
+/// Number of queries from each listed mismatch that are loaded from LMDB as examples.
+const SAMPLE_QUERIES: usize = 3;
+
+#[derive(Debug, Args)]
+pub struct DiffSum {
+    /// Path to JSON datafile.
+    #[arg(short, long, value_name = "FILE")]
+    datafile: Option<PathBuf>,
+
+    /// Ignore reproducibility data from diff-repro.
+    #[arg(long)]
+    without_diffrepro: bool,
+
+    /// Minimal ratio of successful reproductions for a mismatch to be counted.
+    #[arg(long, value_name = "RATIO", default_value_t = 1.0)]
+    reproducibility_threshold: f64,
+
+    /// Maximum number of mismatches listed for each field.
+    #[arg(short, long, value_name = "N", default_value_t = 10)]
+    limit: usize,
+
+    /// Output format.
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Write the summary to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Reference statistics from sum-stat; counts outside of their range are reported.
+    #[arg(short, long, value_name = "FILE")]
+    stats: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Html,
+    Markdown,
+    Junit,
+}
+
+impl DiffSum {
+    /// Load example queries for listed mismatches from LMDB.
+    fn load_samples(
+        &self,
+        args: &Respdiff,
+        config: &Config,
+        summary: &Summary,
+    ) -> Result<BTreeMap<QKey, QuerySample>> {
+        let (env, EnvInfo { format, order }) = args.checked_env(config)?;
+        let qdb = database::open_db(&env, queriesdb::NAME, false)?;
+        let adb = database::open_db(&env, answersdb::NAME, false)?;
+        let txn = env.begin_ro_txn()?;
+
+        let mut samples = BTreeMap::new();
+        for fdis in summary.fields().values() {
+            for mmqueries in top_mismatches(fdis, self.limit) {
+                for key in mmqueries.queries.iter().take(SAMPLE_QUERIES) {
+                    let mut sample = QuerySample::load(qdb, adb, &txn, *key, format)?;
+                    // responses are stored in LMDB order, but labeled by configured servers
+                    if sample.responses.len() == order.len() {
+                        sample.responses =
+                            order.iter().map(|i| sample.responses[*i].clone()).collect();
+                    }
+                    samples.insert(*key, sample);
+                }
+            }
+        }
+        Ok(samples)
+    }
+
+    /// Load example queries, or fall back to questions embedded in the report if LMDB isn't
+    /// available.
+    fn samples(
+        &self,
+        args: &Respdiff,
+        config: &Config,
+        report: &Report,
+        summary: &Summary,
+    ) -> BTreeMap<QKey, QuerySample> {
+        self.load_samples(args, config, summary)
+            .unwrap_or_else(|e| {
+                warn!("unable to load example queries from LMDB: {}", e);
+                embedded_samples(report)
+            })
+    }
+
+    /// Load reference statistics and return outliers of the summary.
+    fn outliers(&self, summary: &Summary) -> Result<Vec<Outlier>> {
+        let path = match &self.stats {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let file =
+            File::open(path).map_err(|e| anyhow!("failed to open {}: {}", path.display(), e))?;
+        let stats: SummaryStatistics = serde_json::from_reader(BufReader::new(file))
+            .map_err(|e| anyhow!("failed to read {}: {}", path.display(), e))?;
+        Ok(stats.outliers(summary))
+    }
+
+    fn write(
+        &self,
+        out: &mut impl Write,
+        args: &Respdiff,
+        config: &Config,
+        report: &Report,
+        summary: &Summary,
+        outliers: &[Outlier],
+    ) -> Result<()> {
+        match self.format {
+            Format::Text => {
+                text::write_text(
+                    out,
+                    report,
+                    summary,
+                    &config.report.field_weights,
+                    self.limit,
+                )?;
+                if self.stats.is_some() {
+                    text::write_outliers(out, outliers)?;
+                }
+            }
+            Format::Html => html::write_html(
+                out,
+                report,
+                summary,
+                &config.report.field_weights,
+                self.limit,
+                &config.servers,
+                &self.samples(args, config, report, summary),
+            )?,
+            Format::Markdown => markdown::write_markdown(
+                out,
+                report,
+                summary,
+                &config.report.field_weights,
+                self.limit,
+                &self.samples(args, config, report, summary),
+            )?,
+            Format::Junit => junit::write_junit(
+                out,
+                summary,
+                &config.report.field_weights,
+                &self.samples(args, config, report, summary),
+            )?,
+        }
+        Ok(())
+    }
+}
+
+impl Executable for DiffSum {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let config = args.config()?;
+        let datafile = args.datafile(&self.datafile)?;
+
+        let mut report = Report::from_file(&datafile)?;
+        let threshold = (!self.without_diffrepro).then_some(self.reproducibility_threshold);
+        let summary = Summary::from_report(&report, &config.report.field_weights, threshold);
+        let outliers = self.outliers(&summary)?;
+        if !matches!(self.format, Format::Text) {
+            for outlier in &outliers {
+                warn!(
+                    "{} count {} outside of reference range {}-{}",
+                    outlier.name, outlier.count, outlier.min, outlier.max
+                );
+            }
+        }
+
+        match &self.output {
+            Some(path) => {
+                let mut out = BufWriter::new(File::create(path)?);
+                self.write(&mut out, args, &config, &report, &summary, &outliers)?;
+                out.flush()?;
+            }
+            None => self.write(
+                &mut io::stdout().lock(),
+                args,
+                &config,
+                &report,
+                &summary,
+                &outliers,
+            )?,
+        }
+
+        report.summary = Some(summary);
+        let out = File::create(datafile)?;
+        serde_json::to_writer(&out, &report)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::Args;
+use lmdb::{Cursor, Error as LmdbError, Transaction};
+use respdiff::{
+    database::{self, answersdb, metadb, queriesdb},
+    error::Error,
+    output::dump::{self, DumpEntry},
+};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct Dump {
+    /// Write to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+impl Executable for Dump {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let env = args.env()?;
+        let mdb = database::open_db(&env, metadb::NAME, false)?;
+        let qdb = database::open_db(&env, queriesdb::NAME, false)?;
+        let adb = database::open_db(&env, answersdb::NAME, false)?;
+        let txn = env.begin_ro_txn()?;
+        let format = metadb::check_version(mdb, &txn)?;
+        // responses are labeled by servers in LMDB order, so no config is needed
+        let servers = metadb::read_servers(mdb, &txn)?;
+
+        let mut out: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+            None => Box::new(BufWriter::new(io::stdout().lock())),
+        };
+        let mut cur = txn.open_ro_cursor(qdb)?;
+        for res in cur.iter() {
+            let query = queriesdb::Query::from(res?);
+            let responses = match answersdb::get_response_list(adb, &txn, query.key, format) {
+                Ok(list) => list.replies,
+                Err(Error::Database(LmdbError::NotFound)) => Vec::new(),
+                Err(e) => return Err(e.into()),
+            };
+            dump::write_entry(&mut out, &DumpEntry::new(&query, &responses, &servers))?;
+        }
+        out.flush()?;
+
+        Ok(())
+    }
+}
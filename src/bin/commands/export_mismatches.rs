@@ -0,0 +1,92 @@
+extern crate lmdb;
+
+use anyhow::Result;
+use clap::{Args, ValueEnum};
+use log::warn;
+use respdiff::{
+    database::{self, queriesdb},
+    dataformat::Report,
+    output::{csv, load_questions},
+};
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct ExportMismatches {
+    /// Path to JSON datafile.
+    #[arg(short, long, value_name = "FILE")]
+    datafile: Option<PathBuf>,
+
+    /// Output format.
+    #[arg(short, long, value_enum, default_value_t = Format::Csv)]
+    format: Format,
+
+    /// Write to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Don't decode query names from LMDB.
+    #[arg(long)]
+    no_questions: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Csv,
+    Tsv,
+}
+
+impl Executable for ExportMismatches {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let datafile = args.datafile(&self.datafile)?;
+        let report = Report::from_file(&datafile)?;
+
+        let questions = if self.no_questions {
+            None
+        } else {
+            let questions = args.env().and_then(|env| {
+                let qdb = database::open_db(&env, queriesdb::NAME, false)?;
+                let txn = env.begin_ro_txn()?;
+                Ok(load_questions(
+                    qdb,
+                    &txn,
+                    report.target_disagreeing_queries(),
+                )?)
+            });
+            match questions {
+                Ok(questions) => Some(questions),
+                Err(e) if !report.questions.is_empty() => {
+                    warn!("unable to load queries from LMDB, using report: {}", e);
+                    Some(report.questions.clone())
+                }
+                Err(e) => {
+                    warn!("unable to load queries from LMDB: {}", e);
+                    None
+                }
+            }
+        };
+
+        let delimiter = match self.format {
+            Format::Csv => ',',
+            Format::Tsv => '\t',
+        };
+        match &self.output {
+            Some(path) => {
+                let mut out = BufWriter::new(File::create(path)?);
+                csv::write_csv(&mut out, &report, delimiter, questions.as_ref())?;
+                out.flush()?;
+            }
+            None => csv::write_csv(
+                &mut io::stdout().lock(),
+                &report,
+                delimiter,
+                questions.as_ref(),
+            )?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::Result;
+use clap::Args;
+use lmdb::Error as LmdbError;
+use log::warn;
+use respdiff::{
+    database::{self, answersdb, metadb, queriesdb, EnvInfo},
+    error::Error,
+    output,
+    pcap::{PcapWriter, LINKTYPE_RAW},
+};
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct ExportPcap {
+    /// Output pcap file.
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+}
+
+impl Executable for ExportPcap {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let config = args.config()?;
+        let (env, EnvInfo { format, order }) = args.checked_env(&config)?;
+        let servers: Vec<SocketAddr> = config
+            .servers
+            .iter()
+            .map(|name| {
+                let server = &config.server_data[name];
+                SocketAddr::new(server.ip, server.port)
+            })
+            .collect();
+
+        let mdb = database::open_db(&env, metadb::NAME, false)?;
+        let qdb = database::open_db(&env, queriesdb::NAME, false)?;
+        let adb = database::open_db(&env, answersdb::NAME, false)?;
+        let txn = env.begin_ro_txn()?;
+        let start = match metadb::read_start_time(mdb, &txn) {
+            Ok(time) => Duration::from_secs(time as u64),
+            Err(Error::Database(LmdbError::NotFound)) => {
+                warn!("meta db is missing start time, timestamps start at Unix epoch");
+                Duration::ZERO
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut out = PcapWriter::new(BufWriter::new(File::create(&self.output)?), LINKTYPE_RAW)?;
+        for query in queriesdb::get_queries(qdb, &txn)? {
+            let list = match answersdb::get_raw_response_list(adb, &txn, query.key, format) {
+                Ok(list) if list.responses.len() == order.len() => Some(list),
+                Ok(_) => {
+                    warn!("{}: unexpected number of responses, skipped", query.key);
+                    None
+                }
+                Err(Error::Database(LmdbError::NotFound)) => None,
+                Err(e) => return Err(e.into()),
+            };
+            // responses are stored in LMDB order, but addressed by configured servers
+            let (responses, meta) = match list {
+                Some(list) => (
+                    order.iter().map(|i| list.responses[*i].clone()).collect(),
+                    order
+                        .iter()
+                        .filter_map(|i| list.meta.get(*i).copied())
+                        .collect(),
+                ),
+                None => (Vec::new(), Vec::new()),
+            };
+            output::pcap::write_exchange(
+                &mut out,
+                query.key,
+                &query.wire,
+                &servers,
+                &responses,
+                &meta,
+                start,
+            )?;
+        }
+        out.into_inner()?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use respdiff::dataformat::Report;
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct MergeReports {
+    /// JSON datafiles to merge, e.g. from runs on different shards of a query set.
+    #[arg(value_name = "REPORT", required = true)]
+    reports: Vec<PathBuf>,
+
+    /// Path to the merged JSON datafile.
+    #[arg(short, long, value_name = "FILE")]
+    output: PathBuf,
+}
+
+impl Executable for MergeReports {
+    fn exec(&self, _args: &Respdiff) -> Result<()> {
+        let mut paths = self.reports.iter();
+        let first = paths.next().ok_or_else(|| anyhow!("no reports to merge"))?;
+        let mut merged = Report::from_file(first)?;
+        for path in paths {
+            let report = Report::from_file(path)?;
+            merged
+                .merge(&report)
+                .map_err(|e| anyhow!("failed to merge {}: {}", path.display(), e))?;
+        }
+
+        let out = File::create(&self.output)?;
+        serde_json::to_writer(&out, &merged)?;
+        Ok(())
+    }
+}
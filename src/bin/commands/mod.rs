@@ -3,13 +3,27 @@ use clap::{Args, Parser, Subcommand};
 use lmdb::Environment;
 
 use std::env;
-use std::path::PathBuf;
+use std::io;
+use std::path::{Path, PathBuf};
 
-use respdiff::config::Config;
-use respdiff::database;
+use respdiff::config::{self, Config, LmdbConfig};
+use respdiff::database::{self, EnvInfo};
+use respdiff::dataformat::{Report, Summary};
+use respdiff::error::Error;
 
+mod check_db;
+mod compare_reports;
 mod diff_answers;
+mod diff_repro;
+mod diff_sum;
+mod dump;
+mod export_mismatches;
+mod export_pcap;
+mod merge_reports;
+mod qprep;
+mod sum_stat;
 mod transceive;
+mod zone_prep;
 
 pub trait Executable {
     fn exec(&self, args: &Respdiff) -> Result<()>;
@@ -36,25 +50,118 @@ impl Respdiff {
             None => Ok(env::current_dir()?),
         }
     }
+    /// Return LMDB parameters from config (defaults if it doesn't exist), overridden by command
+    /// line.
+    pub fn lmdb_config(&self) -> Result<LmdbConfig> {
+        let opts = &self.global_opts;
+        let mut lmdb = match Config::try_from(&opts.config) {
+            Ok(config) => config.lmdb,
+            Err(Error::ConfigFile(e)) if e.kind() == io::ErrorKind::NotFound => {
+                LmdbConfig::default()
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(map_size) = opts.lmdb_map_size {
+            lmdb.map_size = map_size;
+        }
+        if let Some(max_dbs) = opts.lmdb_max_dbs {
+            lmdb.max_dbs = max_dbs;
+        }
+        if let Some(max_readers) = opts.lmdb_max_readers {
+            lmdb.max_readers = max_readers;
+        }
+        Ok(lmdb)
+    }
     pub fn env(&self) -> Result<Environment> {
         let path = self.envdir()?;
-        Ok(database::open_env(&path)?)
+        Ok(database::open_env_with_config(&path, &self.lmdb_config()?)?)
+    }
+    /// Open LMDB environment and verify its format and servers match the configuration.
+    ///
+    /// Also returns the binary format and the index of each configured server in the stored
+    /// response lists.
+    pub fn checked_env(&self, config: &Config) -> Result<(Environment, EnvInfo)> {
+        let env = self.env()?;
+        let info = database::check_env(&env, &config.servers)?;
+        Ok((env, info))
+    }
+    /// Return the datafile path, defaulting to ``report.json`` in envdir.
+    pub fn datafile(&self, datafile: &Option<PathBuf>) -> Result<PathBuf> {
+        match datafile {
+            Some(path) => Ok(path.clone()),
+            None => {
+                let mut path = self.envdir()?;
+                path.push("report.json");
+                Ok(path)
+            }
+        }
+    }
+}
+
+/// Load summary from datafile, or compute it if the datafile doesn't contain one.
+pub fn load_summary(args: &Respdiff, path: &Path) -> Result<Summary> {
+    let report = Report::from_file(path)?;
+    match report.summary {
+        Some(summary) => Ok(summary),
+        None => {
+            let config = args.config()?;
+            Ok(Summary::from_report(
+                &report,
+                &config.report.field_weights,
+                Some(1.0),
+            ))
+        }
     }
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
+    /// Check LMDB for missing, truncated or malformed records.
+    CheckDb(check_db::CheckDb),
+    /// Compare summaries of two datafiles from different runs.
+    CompareReports(compare_reports::CompareReports),
     /// Find differences between answers.
     DiffAnswers(diff_answers::DiffAnswers),
+    /// Re-send queries with target disagreements and check whether they reproduce.
+    DiffRepro(diff_repro::DiffRepro),
+    /// Summarize differences in the datafile.
+    DiffSum(diff_sum::DiffSum),
+    /// Dump stored queries and responses as JSON Lines.
+    Dump(dump::Dump),
+    /// Export target mismatches of each query as CSV or TSV.
+    ExportMismatches(export_mismatches::ExportMismatches),
+    /// Export queries and responses from LMDB to a pcap file.
+    ExportPcap(export_pcap::ExportPcap),
+    /// Merge datafiles from runs on different shards of a query set.
+    MergeReports(merge_reports::MergeReports),
+    /// Extract DNS queries from pcap files or text lists into LMDB.
+    Qprep(qprep::Qprep),
+    /// Compute reference statistics of summaries from repeated runs.
+    #[command(alias = "sumstat")]
+    SumStat(sum_stat::SumStat),
     /// Send queries to servers and record answers.
     Transceive(transceive::Transceive),
+    /// Generate queries for names and types in a zone file into LMDB.
+    #[command(alias = "zoneprep")]
+    ZonePrep(zone_prep::ZonePrep),
 }
 impl Executable for Command {
     fn exec(&self, args: &Respdiff) -> Result<()> {
         use Command::*;
         match self {
+            CheckDb(cmd) => cmd.exec(args),
+            CompareReports(cmd) => cmd.exec(args),
             DiffAnswers(cmd) => cmd.exec(args),
+            DiffRepro(cmd) => cmd.exec(args),
+            DiffSum(cmd) => cmd.exec(args),
+            Dump(cmd) => cmd.exec(args),
+            ExportMismatches(cmd) => cmd.exec(args),
+            ExportPcap(cmd) => cmd.exec(args),
+            MergeReports(cmd) => cmd.exec(args),
+            Qprep(cmd) => cmd.exec(args),
+            SumStat(cmd) => cmd.exec(args),
             Transceive(cmd) => cmd.exec(args),
+            ZonePrep(cmd) => cmd.exec(args),
         }
     }
 }
@@ -68,4 +175,20 @@ struct GlobalOpts {
     /// LMDB environment directory.
     #[arg(short, long, value_name = "DIR", global = true)]
     envdir: Option<PathBuf>,
+
+    /// Initial LMDB map size in bytes, with optional K, M, G or T suffix.
+    #[arg(long, value_name = "SIZE", global = true, value_parser = parse_size)]
+    lmdb_map_size: Option<usize>,
+
+    /// Maximum number of LMDB databases.
+    #[arg(long, value_name = "N", global = true)]
+    lmdb_max_dbs: Option<u32>,
+
+    /// Maximum number of LMDB readers.
+    #[arg(long, value_name = "N", global = true)]
+    lmdb_max_readers: Option<u32>,
+}
+
+fn parse_size(value: &str) -> Result<usize, String> {
+    config::parse_size(value).map_err(|e| e.to_string())
 }
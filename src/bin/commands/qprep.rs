@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use byteorder::{ByteOrder, LittleEndian};
+use clap::{Args, ValueEnum};
+use lmdb::Environment;
+use log::info;
+use respdiff::{
+    database::{self, queriesdb},
+    pcap::PcapReader,
+    qprep::{self, QueryCollector, QueryOptions},
+};
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+
+use crate::commands::{Executable, Respdiff};
+
+/// Number of queries written to LMDB in a single transaction.
+const WRITE_BATCH: usize = 1000;
+
+#[derive(Debug, Args)]
+pub struct Qprep {
+    /// Input files, see --format.
+    #[arg(value_name = "FILE", required = true)]
+    files: Vec<PathBuf>,
+
+    /// Input format: pcap or pcapng captures, or text lists with lines like
+    /// "example.com. AAAA +dnssec +cd".
+    #[arg(short, long, value_enum, default_value_t = Format::Pcap)]
+    format: Format,
+
+    /// Keep only the first query with the same qname, qtype and qclass.
+    #[arg(long)]
+    dedup: bool,
+
+    /// Extract only queries sent to this port.
+    #[arg(long, value_name = "PORT", default_value_t = 53)]
+    port: u16,
+
+    #[clap(flatten)]
+    query: QueryArgs,
+}
+
+/// Header flags and EDNS parameters of generated queries.
+#[derive(Debug, Args)]
+pub struct QueryArgs {
+    /// Don't set the RD flag in generated queries (per text list line: +rd, +nord).
+    #[arg(long)]
+    no_rd: bool,
+
+    /// Set the DO bit in generated queries (per text list line: +dnssec, +nodnssec).
+    #[arg(long)]
+    dnssec: bool,
+
+    /// Set the CD flag in generated queries (per text list line: +cd, +nocd).
+    #[arg(long)]
+    cd: bool,
+
+    /// EDNS buffer size of generated queries (per text list line: +bufsize=N).
+    #[arg(long, value_name = "N")]
+    bufsize: Option<u16>,
+}
+
+impl QueryArgs {
+    pub fn options(&self) -> QueryOptions {
+        QueryOptions {
+            rd: !self.no_rd,
+            dnssec: self.dnssec,
+            cd: self.cd,
+            bufsize: self.bufsize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Pcap,
+    Text,
+}
+
+impl Executable for Qprep {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let env = new_queries_env(args)?;
+        let options = self.query.options();
+        let mut collector = QueryCollector::new(self.port, self.dedup);
+        for path in &self.files {
+            let file = File::open(path)
+                .map_err(|e| anyhow!("failed to open {}: {}", path.display(), e))?;
+            let reader = BufReader::new(file);
+            match self.format {
+                Format::Pcap => {
+                    let reader = PcapReader::new(reader)
+                        .map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+                    for packet in reader {
+                        let packet = packet.map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+                        collector.add_packet(&packet);
+                    }
+                }
+                Format::Text => {
+                    for (i, line) in reader.lines().enumerate() {
+                        let query = qprep::parse_query_line(&line?, &options)
+                            .map_err(|e| anyhow!("{}:{}: {}", path.display(), i + 1, e))?;
+                        if let Some(wire) = query {
+                            collector.add_query(wire);
+                        }
+                    }
+                }
+            }
+        }
+        let queries = collector.finish();
+        write_queries(&env, &queries)
+    }
+}
+
+/// Open LMDB environment, creating its directory, and check it doesn't contain queries yet.
+pub fn new_queries_env(args: &Respdiff) -> Result<Environment> {
+    fs::create_dir_all(args.envdir()?)?;
+    let env = args.env()?;
+    if database::exists_db(&env, queriesdb::NAME)? {
+        return Err(anyhow!("queries database already exists"));
+    }
+    Ok(env)
+}
+
+/// Write queries to ``queries`` db, with keys assigned sequentially starting at 1.
+pub fn write_queries(env: &Environment, queries: &[Vec<u8>]) -> Result<()> {
+    let qdb = database::open_db(env, queriesdb::NAME, true)?;
+    for (i, chunk) in queries.chunks(WRITE_BATCH).enumerate() {
+        let batch: Vec<_> = chunk
+            .iter()
+            .enumerate()
+            .map(|(j, wire)| {
+                let mut key_buf = [0; 4];
+                LittleEndian::write_u32(&mut key_buf, (i * WRITE_BATCH + j + 1) as u32);
+                (key_buf, wire)
+            })
+            .collect();
+        database::write_batch(env, qdb, &batch)?;
+    }
+    info!("{} queries written", queries.len());
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use anyhow::Result;
+use clap::Args;
+use respdiff::sumstat::SummaryStatistics;
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+
+use crate::commands::{load_summary, Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct SumStat {
+    /// JSON datafiles from repeated runs of the same configuration.
+    #[arg(value_name = "REPORT", required = true)]
+    reports: Vec<PathBuf>,
+
+    /// Write reference statistics to a file instead of stdout.
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+impl Executable for SumStat {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let summaries = self
+            .reports
+            .iter()
+            .map(|path| load_summary(args, path))
+            .collect::<Result<Vec<_>>>()?;
+        let stats = SummaryStatistics::new(&summaries);
+
+        match &self.output {
+            Some(path) => {
+                let mut out = BufWriter::new(File::create(path)?);
+                serde_json::to_writer_pretty(&mut out, &stats)?;
+                out.flush()?;
+            }
+            None => {
+                let mut out = io::stdout().lock();
+                serde_json::to_writer_pretty(&mut out, &stats)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -3,32 +3,64 @@ extern crate lmdb;
 use anyhow::Result;
 use async_std::{prelude::*, task};
 use byteorder::{ByteOrder, LittleEndian};
-use clap::Args;
+use clap::{Args, ValueEnum};
 use futures::channel::mpsc;
-use lmdb::{Transaction, WriteFlags};
+use lmdb::Transaction;
 use log::warn;
 use respdiff::{
-    database::{self, queriesdb},
-    transceive,
+    database::{self, answersdb, queriesdb, BinFormat},
+    output::metrics::{self, ServerStats},
+    transceive, ServerResponse,
 };
 
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::time::Duration;
 
 use crate::commands::{Executable, Respdiff};
 
+/// Number of response lists written to LMDB in a single transaction.
+const WRITE_BATCH: usize = 1000;
+
 #[derive(Debug, Args)]
-pub struct Transceive {}
+pub struct Transceive {
+    /// Write run statistics in OpenMetrics text format to a file.
+    #[arg(long, value_name = "FILE")]
+    metrics: Option<PathBuf>,
+
+    /// Binary format of the answers database. v2 also stores transport, send time, retries and
+    /// failure reason of each response, but can't be read by Python respdiff.
+    #[arg(long, value_enum, default_value_t = Format::V1)]
+    format: Format,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    V1,
+    V2,
+}
+
+impl From<Format> for BinFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::V1 => BinFormat::V1,
+            Format::V2 => BinFormat::V2,
+        }
+    }
+}
 
 impl Executable for Transceive {
     fn exec(&self, args: &Respdiff) -> Result<()> {
         warn!("SUBCOMMAND transceive IS AN UNFINISHED PROTOTYPE!");
         let config = args.config()?;
+        let format = BinFormat::from(self.format);
         let env = args.env()?;
         let metadb = database::open_db(&env, database::metadb::NAME, true)?;
         {
             let mut txn = env.begin_rw_txn()?;
             database::metadb::write_servers(metadb, &mut txn, config.servers.clone())?;
-            database::metadb::write_version(metadb, &mut txn)?;
+            database::metadb::write_version(metadb, &mut txn, format)?;
             database::metadb::write_start_time(metadb, &mut txn)?;
             txn.commit()?;
         }
@@ -40,13 +72,17 @@ impl Executable for Transceive {
         //}
 
         let qdb = database::open_db(&env, database::queriesdb::NAME, false)?;
-        let txn = env.begin_ro_txn()?;
-        let queries = queriesdb::get_queries(qdb, &txn)?;
+        let queries = {
+            // no transaction may stay open, so that the map can grow during writes
+            let txn = env.begin_ro_txn()?;
+            queriesdb::get_queries(qdb, &txn)?
+        };
+        let total_queries = queries.len() as u64;
 
         let servers = config
             .servers
             .iter()
-            .map(|name| *config.server_data.get(name).unwrap())
+            .map(|name| config.server_data.get(name).unwrap().clone())
             .collect();
         let (rsender, mut rreceiver) = mpsc::unbounded();
         task::spawn(transceive::send_loop(
@@ -57,23 +93,41 @@ impl Executable for Transceive {
             800, // TODO hardcoded qps
         ));
 
+        let mut stats: Vec<_> = config.servers.iter().map(|n| ServerStats::new(n)).collect();
+        let mut total_answers: u64 = 0;
         let adb = database::open_db(&env, database::answersdb::NAME, true)?;
-        let mut txn = env.begin_rw_txn()?;
-        task::block_on(async move {
+        task::block_on(async {
+            let mut batch = Vec::with_capacity(WRITE_BATCH);
             while let Some(responselist) = rreceiver.next().await {
+                total_answers += 1;
+                for (i, response) in responselist.responses.iter().enumerate() {
+                    stats[i].add(&ServerResponse::from(response.clone()));
+                }
                 let key = responselist.key;
                 let mut key_buf = [0; 4];
                 LittleEndian::write_u32(&mut key_buf, key);
-                let data: Vec<u8> = responselist.into();
-                txn.put(adb, &key_buf, &data, WriteFlags::empty()).unwrap(); // TODO error handling?
+                let data = answersdb::serialize_response_list(responselist, format);
+                batch.push((key_buf, data));
+                if batch.len() >= WRITE_BATCH {
+                    database::write_batch(&env, adb, &batch)?;
+                    batch.clear();
+                }
             }
-            txn.commit().unwrap(); // TODO err handle?
-        });
+            database::write_batch(&env, adb, &batch)
+        })?;
 
         let mut txn = env.begin_rw_txn()?;
         database::metadb::write_end_time(metadb, &mut txn)?;
         txn.commit()?;
 
+        if let Some(path) = &self.metrics {
+            let mut out = BufWriter::new(File::create(path)?);
+            metrics::write_totals(&mut out, total_queries, total_answers)?;
+            metrics::write_servers(&mut out, &mut stats)?;
+            metrics::write_eof(&mut out)?;
+            out.flush()?;
+        }
+
         Ok(())
     }
 }
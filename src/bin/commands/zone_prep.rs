@@ -0,0 +1,68 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use domain::base::name::Dname;
+use rand::{rngs::StdRng, SeedableRng};
+use respdiff::zone::Zone;
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::commands::qprep::{new_queries_env, write_queries, QueryArgs};
+use crate::commands::{Executable, Respdiff};
+
+#[derive(Debug, Args)]
+pub struct ZonePrep {
+    /// Zone file in master format.
+    #[arg(value_name = "ZONE")]
+    zonefile: PathBuf,
+
+    /// Origin of relative names, unless set by $ORIGIN in the zone file.
+    #[arg(long, value_name = "NAME")]
+    origin: Option<String>,
+
+    /// Number of queries for random names matching each wildcard record.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    wildcard: usize,
+
+    /// Number of queries for random nonexistent names.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    nxdomain: usize,
+
+    /// Seed of random names, to generate the same query set repeatedly.
+    #[arg(long, value_name = "N")]
+    seed: Option<u64>,
+
+    #[clap(flatten)]
+    query: QueryArgs,
+}
+
+impl Executable for ZonePrep {
+    fn exec(&self, args: &Respdiff) -> Result<()> {
+        let env = new_queries_env(args)?;
+        let origin = match &self.origin {
+            Some(origin) => Some(
+                Dname::from_str(origin).map_err(|e| anyhow!("invalid origin {}: {}", origin, e))?,
+            ),
+            None => None,
+        };
+        let text = fs::read_to_string(&self.zonefile)
+            .map_err(|e| anyhow!("failed to read {}: {}", self.zonefile.display(), e))?;
+        let zone = Zone::parse(&text, origin)
+            .map_err(|e| anyhow!("{}: {}", self.zonefile.display(), e))?;
+
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let queries = zone
+            .queries(
+                &self.query.options(),
+                &mut rng,
+                self.wildcard,
+                self.nxdomain,
+            )
+            .map_err(|e| anyhow!("{}: {}", self.zonefile.display(), e))?;
+        write_queries(&env, &queries)
+    }
+}
@@ -1,6 +1,7 @@
-use crate::{error::Error, DiffCriteria};
+use crate::{error::Error, matcher::Field, DiffCriteria};
 use serde::{Deserialize, Deserializer};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs::File;
 use std::io::BufReader;
 use std::net::IpAddr;
@@ -12,6 +13,8 @@ pub struct Config {
     pub sendrecv: SendRecvConfig,
     pub diff: DiffConfig,
     pub report: ReportConfig,
+    #[serde(default)]
+    pub lmdb: LmdbConfig,
     #[serde(deserialize_with = "servers_from_namelist")]
     pub servers: Vec<String>,
     #[serde(flatten)]
@@ -57,13 +60,62 @@ pub struct SendRecvConfig {
     pub max_timeouts: Option<u64>,
 }
 
+/// LMDB environment configuration
+#[derive(Deserialize, PartialEq, Eq, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct LmdbConfig {
+    /// Initial map size in bytes, grown automatically when it fills up during writes.
+    #[serde(deserialize_with = "size_from_str")]
+    pub map_size: usize,
+    pub max_dbs: u32,
+    pub max_readers: u32,
+}
+
+impl Default for LmdbConfig {
+    fn default() -> Self {
+        LmdbConfig {
+            map_size: 10 * 1024_usize.pow(3), // 10 G
+            max_dbs: 5,
+            max_readers: 384,
+        }
+    }
+}
+
+/// Parse size in bytes with an optional K, M, G or T (binary) suffix.
+pub fn parse_size(value: &str) -> Result<usize, Error> {
+    let value = value.trim();
+    let (digits, exp) = match value.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&value[..value.len() - 1], 1),
+        Some('M') => (&value[..value.len() - 1], 2),
+        Some('G') => (&value[..value.len() - 1], 3),
+        Some('T') => (&value[..value.len() - 1], 4),
+        _ => (value, 0),
+    };
+    digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(1024_usize.pow(exp)))
+        .ok_or_else(|| Error::InvalidSize(value.to_string()))
+}
+
+fn size_from_str<'de, D>(deserializer: D) -> Result<usize, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s: String = Deserialize::deserialize(deserializer)?;
+    parse_size(&s).map_err(serde::de::Error::custom)
+}
+
 /// Single server configuration
-#[derive(Deserialize, PartialEq, Debug, Copy, Clone)]
+#[derive(Deserialize, PartialEq, Debug, Clone)]
 pub struct ServerConfig {
     pub ip: IpAddr,
     #[serde(deserialize_with = "port_from_str")]
     pub port: u16,
     pub transport: TransportProtocol,
+    /// Script to clean cache and restart the server, used by diffrepro.
+    pub restart_script: Option<PathBuf>,
 }
 
 fn port_from_str<'de, D>(deserializer: D) -> Result<u16, D::Error>
@@ -75,9 +127,10 @@ where
 }
 
 /// Transport protocol used to send/receive queries
-#[derive(Deserialize, PartialEq, Debug, Copy, Clone)]
+#[derive(Deserialize, PartialEq, Eq, Debug, Copy, Clone, Default)]
 #[serde(try_from = "String")]
 pub enum TransportProtocol {
+    #[default]
     Udp,
     Tcp,
     Tls,
@@ -96,6 +149,17 @@ impl TryFrom<String> for TransportProtocol {
     }
 }
 
+impl fmt::Display for TransportProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TransportProtocol::Udp => "udp",
+            TransportProtocol::Tcp => "tcp",
+            TransportProtocol::Tls => "tls",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 /// Msgdiff configuration
 #[derive(Deserialize, PartialEq, Eq, Debug, Clone)]
 pub struct DiffConfig {
@@ -132,6 +196,20 @@ impl TryFrom<String> for DiffCriteria {
     }
 }
 
+impl fmt::Display for DiffCriteria {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DiffCriteria::Opcode => "opcode",
+            DiffCriteria::Rcode => "rcode",
+            DiffCriteria::Flags => "flags",
+            DiffCriteria::Question => "question",
+            DiffCriteria::AnswerTypes => "answertypes",
+            DiffCriteria::AnswerRrsigs => "answerrrsigs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 fn criteria_from_list<'de, D>(deserializer: D) -> Result<Vec<DiffCriteria>, D::Error>
 where
     D: Deserializer<'de>,
@@ -201,6 +279,42 @@ impl TryFrom<String> for FieldWeight {
     }
 }
 
+impl From<Field> for FieldWeight {
+    fn from(field: Field) -> Self {
+        match field {
+            Field::Timeout => FieldWeight::Timeout,
+            Field::Malformed => FieldWeight::Malformed,
+            Field::Opcode => FieldWeight::Opcode,
+            Field::Rcode => FieldWeight::Rcode,
+            Field::Flags => FieldWeight::Flags,
+            Field::Question => FieldWeight::Question,
+            Field::AnswerTypes => FieldWeight::AnswerTypes,
+            Field::AnswerRrsigs => FieldWeight::AnswerRrsigs,
+        }
+    }
+}
+
+impl fmt::Display for FieldWeight {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldWeight::Timeout => "timeout",
+            FieldWeight::Malformed => "malformed",
+            FieldWeight::Opcode => "opcode",
+            FieldWeight::Question => "question",
+            FieldWeight::Rcode => "rcode",
+            FieldWeight::Flags => "flags",
+            FieldWeight::AnswerTypes => "answertypes",
+            FieldWeight::AnswerRrsigs => "answerrrsigs",
+            FieldWeight::Answer => "answer",
+            FieldWeight::Authority => "authority",
+            FieldWeight::Additional => "additional",
+            FieldWeight::Edns => "edns",
+            FieldWeight::Nsid => "nsid",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 fn field_weights_from_list<'de, D>(deserializer: D) -> Result<Vec<FieldWeight>, D::Error>
 where
     D: Deserializer<'de>,
@@ -217,7 +331,7 @@ where
 mod tests {
     use super::*;
 
-    const TEST_INPUT: &'static str = "
+    const TEST_INPUT: &str = "
 [sendrecv]
 # in seconds (float)
 timeout = 16
@@ -268,6 +382,35 @@ criteria = opcode, rcode, flags, question, answertypes, answerrrsigs
 # diffsum reports mismatches in field values in this order
 # if particular message has multiple mismatches, it is counted only once into category with highest weight
 field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes, answerrrsigs, answer, authority, additional, edns, nsid
+";
+
+    const RESTART_INPUT: &str = "
+[sendrecv]
+timeout = 1
+jobs = 1
+time_delay_min = 0
+time_delay_max = 0
+
+[servers]
+names = kresd, unbound
+
+[kresd]
+ip = 127.0.0.1
+port = 53
+transport = udp
+restart_script = /usr/local/bin/restart-kresd
+
+[unbound]
+ip = 127.0.0.2
+port = 53
+transport = udp
+
+[diff]
+target = kresd
+criteria = rcode
+
+[report]
+field_weights = timeout, rcode
 ";
 
     fn expected() -> Config {
@@ -279,6 +422,7 @@ field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes,
                 time_delay_max: 0.0,
                 max_timeouts: Some(10),
             },
+            lmdb: LmdbConfig::default(),
             diff: DiffConfig {
                 target: "cznic".to_string(),
                 criteria: vec![
@@ -319,6 +463,7 @@ field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes,
                         ip: "185.43.135.1".parse().unwrap(),
                         port: 53,
                         transport: TransportProtocol::Udp,
+                        restart_script: None,
                     },
                 ),
                 (
@@ -327,6 +472,7 @@ field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes,
                         ip: "8.8.8.8".parse().unwrap(),
                         port: 53,
                         transport: TransportProtocol::Tcp,
+                        restart_script: None,
                     },
                 ),
                 (
@@ -335,6 +481,7 @@ field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes,
                         ip: "1.1.1.1".parse().unwrap(),
                         port: 853,
                         transport: TransportProtocol::Tls,
+                        restart_script: None,
                     },
                 ),
             ]
@@ -344,6 +491,41 @@ field_weights = timeout, malformed, opcode, question, rcode, flags, answertypes,
         }
     }
 
+    #[test]
+    fn lmdb_section() {
+        let input = format!(
+            "{}\n[lmdb]\nmap_size = 512M\nmax_readers = 16\n",
+            TEST_INPUT
+        );
+        let config = serde_ini::from_str::<Config>(&input).unwrap();
+        assert_eq!(
+            config.lmdb,
+            LmdbConfig {
+                map_size: 512 * 1024 * 1024,
+                max_dbs: 5,
+                max_readers: 16,
+            }
+        );
+        assert!(!config.server_data.contains_key("lmdb"));
+
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("2g"), Ok(2 * 1024_usize.pow(3)));
+        assert_eq!(
+            parse_size("lots"),
+            Err(Error::InvalidSize("lots".to_string()))
+        );
+    }
+
+    #[test]
+    fn restart_script() {
+        let config = serde_ini::from_str::<Config>(RESTART_INPUT).unwrap();
+        assert_eq!(
+            config.server_data["kresd"].restart_script,
+            Some(PathBuf::from("/usr/local/bin/restart-kresd"))
+        );
+        assert_eq!(config.server_data["unbound"].restart_script, None);
+    }
+
     #[test]
     fn test_de() {
         assert_eq!(
@@ -1,22 +1,89 @@
-use lmdb::{Database, DatabaseFlags, Environment, Error as LmdbError};
+use lmdb::{Database, DatabaseFlags, Environment, Error as LmdbError, Transaction, WriteFlags};
 use std::path::Path;
 
-use crate::error::Error;
+use crate::config::LmdbConfig;
+use crate::error::{DbFormatError, Error};
 
-/// Version string of supported respdiff db.
-const BIN_FORMAT_VERSION: &str = "2018-05-21";
+/// Binary format of respdiff db, identified by the version string in ``meta`` db.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum BinFormat {
+    /// Original format, shared with Python respdiff.
+    V1,
+    /// Format with additional details about each response, see `ResponseMeta`.
+    V2,
+}
+
+impl BinFormat {
+    /// Format written by default, readable by Python respdiff.
+    pub const DEFAULT: BinFormat = BinFormat::V1;
 
-/// Create an LMDB Environment.
+    /// Return the version string stored in ``meta`` db.
+    pub fn version(self) -> &'static str {
+        match self {
+            BinFormat::V1 => "2018-05-21",
+            BinFormat::V2 => "2026-10-18",
+        }
+    }
+
+    /// Return the format identified by version string, if supported.
+    pub fn from_version(version: &str) -> Option<Self> {
+        [BinFormat::V1, BinFormat::V2]
+            .into_iter()
+            .find(|format| format.version() == version)
+    }
+}
+
+/// Properties of an environment verified by `check_env()`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EnvInfo {
+    pub format: BinFormat,
+    /// Index of each configured server in the stored response lists.
+    pub order: Vec<usize>,
+}
+
+/// Create an LMDB Environment with default parameters.
 ///
 /// Only a single instance can exist in a process.
 pub fn open_env(dir: &Path) -> Result<Environment, Error> {
+    open_env_with_config(dir, &LmdbConfig::default())
+}
+
+/// Create an LMDB Environment with the given parameters.
+///
+/// Only a single instance can exist in a process.
+pub fn open_env_with_config(dir: &Path, config: &LmdbConfig) -> Result<Environment, Error> {
     Ok(Environment::new()
-        .set_max_dbs(5)
-        .set_map_size(10 * 1024_usize.pow(3)) // 10 G
-        .set_max_readers(384)
+        .set_max_dbs(config.max_dbs)
+        .set_map_size(config.map_size)
+        .set_max_readers(config.max_readers)
         .open(dir)?)
 }
 
+/// Write key-value pairs into a database in a single transaction.
+///
+/// If the map is full, the transaction is aborted, the map size is doubled and the write is
+/// retried. No other transaction may be active in the environment.
+pub fn write_batch<K, V>(env: &Environment, db: Database, items: &[(K, V)]) -> Result<(), Error>
+where
+    K: AsRef<[u8]>,
+    V: AsRef<[u8]>,
+{
+    loop {
+        let mut txn = env.begin_rw_txn()?;
+        let result = items
+            .iter()
+            .try_for_each(|(key, value)| txn.put(db, key, value, WriteFlags::empty()))
+            .and_then(|_| txn.commit());
+        match result {
+            Err(LmdbError::MapFull) => {
+                let map_size = env.info()?.map_size();
+                env.set_map_size(map_size * 2)?;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
 /// Create or open an LMDB database.
 pub fn open_db(env: &Environment, name: &str, create: bool) -> Result<Database, Error> {
     if create {
@@ -35,24 +102,44 @@ pub fn exists_db(env: &Environment, name: &str) -> Result<bool, Error> {
     }
 }
 
+/// Verify that the environment was written in a supported format for the given servers.
+///
+/// Returns the binary format and the index of each configured server in the stored response
+/// lists, see `metadb::reconcile_servers()`.
+pub fn check_env(env: &Environment, servers: &[String]) -> Result<EnvInfo, Error> {
+    let db = match env.open_db(Some(metadb::NAME)) {
+        Ok(db) => db,
+        Err(LmdbError::NotFound) => {
+            return Err(DbFormatError::MissingMetadata(metadb::NAME.to_string()).into())
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let txn = env.begin_ro_txn()?;
+    let format = metadb::check_version(db, &txn)?;
+    let order = metadb::reconcile_servers(db, &txn, servers)?;
+    Ok(EnvInfo { format, order })
+}
+
 /// ``meta`` LMDB and its related data & functions
 pub mod metadb {
+    use super::BinFormat;
     use crate::error::{DbFormatError, Error};
     use byteorder::{ByteOrder, LittleEndian};
-    use lmdb::{Database, RoTransaction, RwTransaction, Transaction, WriteFlags};
+    use lmdb::{
+        Database, Error as LmdbError, RoTransaction, RwTransaction, Transaction, WriteFlags,
+    };
     use std::time::SystemTime;
 
     /// Meta LMDB database name
     pub const NAME: &str = "meta";
 
     /// Write binary format version to LMDB.
-    pub fn write_version(db: Database, txn: &mut RwTransaction) -> Result<(), Error> {
-        Ok(txn.put(
-            db,
-            b"version",
-            &super::BIN_FORMAT_VERSION,
-            WriteFlags::empty(),
-        )?)
+    pub fn write_version(
+        db: Database,
+        txn: &mut RwTransaction,
+        format: BinFormat,
+    ) -> Result<(), Error> {
+        Ok(txn.put(db, b"version", &format.version(), WriteFlags::empty())?)
     }
 
     /// Write start time when transciever started sending queries to LMDB.
@@ -70,10 +157,18 @@ pub mod metadb {
         Ok(txn.put(db, b"start_time", &bytes, WriteFlags::empty())?)
     }
 
+    /// Read a u32 value stored under the key.
+    fn read_u32(db: Database, txn: &RoTransaction, key: &str) -> Result<u32, Error> {
+        let value = txn.get(db, &key)?;
+        if value.len() != 4 {
+            return Err(DbFormatError::InvalidMetadata(key.to_string()).into());
+        }
+        Ok(LittleEndian::read_u32(value))
+    }
+
     /// Read the transciever's start time.
     pub fn read_start_time(db: Database, txn: &RoTransaction) -> Result<u32, Error> {
-        let time = txn.get(db, b"start_time")?;
-        Ok(LittleEndian::read_u32(time))
+        read_u32(db, txn, "start_time")
     }
 
     /// Write end time when transciever finished receiving queries to LMDB.
@@ -93,22 +188,69 @@ pub mod metadb {
 
     /// Read the transceiver's end time.
     pub fn read_end_time(db: Database, txn: &RoTransaction) -> Result<u32, Error> {
-        let time = txn.get(db, b"end_time")?;
-        Ok(LittleEndian::read_u32(time))
+        read_u32(db, txn, "end_time")
+    }
+
+    /// Read binary format version stored in LMDB.
+    pub fn read_version(db: Database, txn: &RoTransaction) -> Result<String, Error> {
+        let version = txn.get(db, b"version")?;
+        Ok(String::from_utf8(version.to_vec())?)
     }
 
     /// Check binary format version.
     ///
-    /// Perform a check that the binary version of particular LMDB is compatible
-    /// with the expected version.
-    pub fn check_version(db: Database, txn: &RoTransaction) -> Result<String, Error> {
-        let version = txn.get(db, b"version")?;
-        let version = String::from_utf8(version.to_vec())?;
+    /// Perform a check that the binary version of particular LMDB is one of the supported
+    /// versions and return its format.
+    pub fn check_version(db: Database, txn: &RoTransaction) -> Result<BinFormat, Error> {
+        let version = match read_version(db, txn) {
+            Err(Error::Database(LmdbError::NotFound)) => {
+                return Err(DbFormatError::MissingMetadata("version".to_string()).into())
+            }
+            result => result?,
+        };
 
-        if version == super::BIN_FORMAT_VERSION {
-            Ok(version)
-        } else {
-            Err(DbFormatError::Unsupported.into())
+        match BinFormat::from_version(&version) {
+            Some(format) => Ok(format),
+            None => Err(DbFormatError::Unsupported {
+                found: version,
+                expected: format!("{}, {}", BinFormat::V1.version(), BinFormat::V2.version()),
+            }
+            .into()),
+        }
+    }
+
+    /// Reconcile the server list stored in LMDB with the configured servers.
+    ///
+    /// Returns the index of each configured server in the stored list, which is also its index
+    /// in the stored response lists. The configured servers may be reordered, but they must be
+    /// the same servers as stored.
+    pub fn reconcile_servers(
+        db: Database,
+        txn: &RoTransaction,
+        servers: &[String],
+    ) -> Result<Vec<usize>, Error> {
+        let stored = match read_servers(db, txn) {
+            Err(Error::Database(LmdbError::NotFound)) => {
+                return Err(DbFormatError::MissingMetadata("servers".to_string()).into())
+            }
+            result => result?,
+        };
+
+        let order: Option<Vec<usize>> = servers
+            .iter()
+            .map(|name| stored.iter().position(|s| s == name))
+            .collect();
+        match order {
+            Some(order)
+                if order.len() == stored.len() && (0..stored.len()).all(|i| order.contains(&i)) =>
+            {
+                Ok(order)
+            }
+            _ => Err(DbFormatError::ServersMismatch {
+                stored,
+                configured: servers.to_vec(),
+            }
+            .into()),
         }
     }
 
@@ -127,13 +269,29 @@ pub mod metadb {
         }
         Ok(())
     }
+
+    /// Read the server list stored in LMDB.
+    pub fn read_servers(db: Database, txn: &RoTransaction) -> Result<Vec<String>, Error> {
+        let count = read_u32(db, txn, "servers")?;
+        (0..count)
+            .map(|i| {
+                let name = txn.get(db, &format!("name{}", i))?;
+                Ok(String::from_utf8(name.to_vec())?)
+            })
+            .collect()
+    }
 }
 
 /// ``queries`` LMDB and its related data & functions
 pub mod queriesdb {
-    use crate::error::Error;
+    use crate::error::{DbFormatError, Error};
     use crate::QKey;
     use byteorder::{ByteOrder, LittleEndian};
+    use domain::base::{
+        name::{Dname, ToDname},
+        question::Question,
+        Message,
+    };
     use lmdb::{Cursor, Database, RoTransaction, Transaction};
     use std::convert::From;
 
@@ -152,6 +310,18 @@ pub mod queriesdb {
         pub wire: Vec<u8>,
     }
 
+    impl Query {
+        /// Parse the question from the query's wire format.
+        pub fn question(&self) -> Result<Question<Dname<Vec<u8>>>, DbFormatError> {
+            let msg = Message::from_octets(self.wire.as_slice())
+                .map_err(|_| DbFormatError::QueryInvalidData)?;
+            match msg.question().next() {
+                Some(Ok(q)) => Ok(Question::new(q.qname().to_vec(), q.qtype(), q.qclass())),
+                _ => Err(DbFormatError::QueryInvalidData),
+            }
+        }
+    }
+
     impl From<(&[u8], &[u8])> for Query {
         fn from(item: (&[u8], &[u8])) -> Self {
             let (key, val) = item;
@@ -162,6 +332,17 @@ pub mod queriesdb {
         }
     }
 
+    /// Retrieve a single query.
+    pub fn get_query(db: Database, txn: &RoTransaction, key: QKey) -> Result<Query, Error> {
+        let mut key_buf = [0; 4];
+        LittleEndian::write_u32(&mut key_buf, key);
+        let wire = txn.get(db, &key_buf)?;
+        Ok(Query {
+            key,
+            wire: wire.to_vec(),
+        })
+    }
+
     /// Retrieve all queries.
     pub fn get_queries(db: Database, txn: &RoTransaction) -> Result<Vec<Query>, Error> {
         let mut cur = txn.open_ro_cursor(db)?;
@@ -176,87 +357,197 @@ pub mod queriesdb {
 
 /// ``answers`` LMDB and its related data & functions
 pub mod answersdb {
+    use super::BinFormat;
     use crate::{
+        config::TransportProtocol,
         error::{DbFormatError, Error},
         transceive::{RawResponse, RawResponseList},
-        DnsReply, ServerResponse, ServerResponseList,
+        FailureKind, QKey, ResponseMeta, ServerResponseList,
     };
     use byteorder::{ByteOrder, LittleEndian};
-    use domain::base::Message;
     use lmdb::{Cursor, Database, RoTransaction, Transaction};
     use std::time::Duration;
 
     /// Answers LMDB database name
     pub const NAME: &str = "answers";
 
-    /// Try to parse servers responses directly from LMDB binary data.
+    /// Length of the per-response header in answers format v1.
+    const HEADER_LEN_V1: usize = 6;
+    /// Length of the per-response header in answers format v2.
+    const HEADER_LEN_V2: usize = 18;
+
+    /// Try to parse servers responses directly from LMDB binary data (format v1).
     impl TryFrom<(&[u8], &[u8])> for ServerResponseList {
         type Error = DbFormatError;
 
         fn try_from(item: (&[u8], &[u8])) -> Result<Self, Self::Error> {
-            let mut replies: Vec<ServerResponse> = vec![];
-            let (key, buf) = item;
-            if key.len() != 4 {
-                return Err(DbFormatError::ReplyInvalidData);
-            }
+            parse_response_list(item, BinFormat::V1)
+        }
+    }
 
-            let mut i = 0;
-            while (i + 6) <= buf.len() {
-                let delay = LittleEndian::read_u32(&buf[i..i + 4]);
-                i += 4;
-                let len = LittleEndian::read_u16(&buf[i..i + 2]) as usize;
-                i += 2;
-
-                if delay == u32::MAX {
-                    if len != 0 {
-                        return Err(DbFormatError::ReplyInvalidData);
-                    } else {
-                        replies.push(ServerResponse::Timeout);
-                        continue;
-                    }
-                }
+    /// Parse servers responses from LMDB binary data in the given format.
+    ///
+    /// Each response starts with a header: u32 delay in microseconds (``u32::MAX`` for no
+    /// response) and u16 length of the DNS message. Format v2 continues the header with u64 send
+    /// time in microseconds since Unix epoch, u8 transport, u8 number of retries, u8 flags
+    /// (TCP fallback) and u8 failure kind.
+    pub fn parse_response_list(
+        item: (&[u8], &[u8]),
+        format: BinFormat,
+    ) -> Result<ServerResponseList, DbFormatError> {
+        parse_raw_response_list(item, format).map(ServerResponseList::from)
+    }
 
-                if i + len > buf.len() {
-                    return Err(DbFormatError::ReplyMissingData);
-                }
+    /// Parse servers responses from LMDB binary data without decoding DNS messages.
+    ///
+    /// See `parse_response_list()` for the binary format.
+    pub fn parse_raw_response_list(
+        item: (&[u8], &[u8]),
+        format: BinFormat,
+    ) -> Result<RawResponseList, DbFormatError> {
+        let mut responses: Vec<RawResponse> = vec![];
+        let mut meta: Vec<ResponseMeta> = vec![];
+        let (key, buf) = item;
+        if key.len() != 4 {
+            return Err(DbFormatError::ReplyInvalidData);
+        }
+        let header_len = match format {
+            BinFormat::V1 => HEADER_LEN_V1,
+            BinFormat::V2 => HEADER_LEN_V2,
+        };
 
-                let wire: Vec<u8> = Vec::from(&buf[i..i + len]);
-                i += len;
-
-                match Message::from_octets(wire) {
-                    Ok(msg) => {
-                        replies.push(ServerResponse::Data(DnsReply {
-                            delay: Duration::from_micros(delay as u64),
-                            message: msg,
-                        }));
-                    }
-                    Err(_) => {
-                        replies.push(ServerResponse::Malformed);
-                    }
+        let mut i = 0;
+        while (i + header_len) <= buf.len() {
+            let delay = LittleEndian::read_u32(&buf[i..i + 4]);
+            let len = LittleEndian::read_u16(&buf[i + 4..i + 6]) as usize;
+            if format == BinFormat::V2 {
+                meta.push(parse_meta(&buf[i + 6..i + HEADER_LEN_V2])?);
+            }
+            i += header_len;
+
+            if delay == u32::MAX {
+                if len != 0 {
+                    return Err(DbFormatError::ReplyInvalidData);
+                } else {
+                    responses.push(RawResponse::Timeout);
+                    continue;
                 }
             }
 
-            if i == buf.len() {
-                Ok(ServerResponseList {
-                    key: LittleEndian::read_u32(key),
-                    replies,
-                })
-            } else {
-                Err(DbFormatError::ReplyMissingData)
+            if i + len > buf.len() {
+                return Err(DbFormatError::ReplyMissingData);
             }
+
+            let wire: Vec<u8> = Vec::from(&buf[i..i + len]);
+            i += len;
+
+            responses.push(RawResponse::Data {
+                delay: Duration::from_micros(delay as u64),
+                wire,
+            });
+        }
+
+        if i == buf.len() {
+            Ok(RawResponseList {
+                key: LittleEndian::read_u32(key),
+                responses,
+                meta,
+            })
+        } else {
+            Err(DbFormatError::ReplyMissingData)
         }
     }
 
+    fn parse_meta(buf: &[u8]) -> Result<ResponseMeta, DbFormatError> {
+        let transport = match buf[8] {
+            0 => TransportProtocol::Udp,
+            1 => TransportProtocol::Tcp,
+            2 => TransportProtocol::Tls,
+            _ => return Err(DbFormatError::ReplyInvalidData),
+        };
+        let failure = match buf[11] {
+            0 => None,
+            1 => Some(FailureKind::Timeout),
+            2 => Some(FailureKind::Refused),
+            3 => Some(FailureKind::Unreachable),
+            4 => Some(FailureKind::Other),
+            _ => return Err(DbFormatError::ReplyInvalidData),
+        };
+        Ok(ResponseMeta {
+            transport,
+            sent_at: LittleEndian::read_u64(&buf[0..8]),
+            retries: buf[9],
+            tcp_fallback: buf[10] & 1 != 0,
+            failure,
+        })
+    }
+
+    fn serialize_meta(meta: &ResponseMeta) -> [u8; HEADER_LEN_V2 - HEADER_LEN_V1] {
+        let mut buf = [0; HEADER_LEN_V2 - HEADER_LEN_V1];
+        LittleEndian::write_u64(&mut buf[0..8], meta.sent_at);
+        buf[8] = match meta.transport {
+            TransportProtocol::Udp => 0,
+            TransportProtocol::Tcp => 1,
+            TransportProtocol::Tls => 2,
+        };
+        buf[9] = meta.retries;
+        buf[10] = meta.tcp_fallback as u8;
+        buf[11] = match meta.failure {
+            None => 0,
+            Some(FailureKind::Timeout) => 1,
+            Some(FailureKind::Refused) => 2,
+            Some(FailureKind::Unreachable) => 3,
+            Some(FailureKind::Other) => 4,
+        };
+        buf
+    }
+
+    /// Retrieve server responses for a single query.
+    pub fn get_response_list(
+        db: Database,
+        txn: &RoTransaction,
+        key: QKey,
+        format: BinFormat,
+    ) -> Result<ServerResponseList, Error> {
+        get_raw_response_list(db, txn, key, format).map(ServerResponseList::from)
+    }
+
+    /// Retrieve server responses for a single query without decoding DNS messages.
+    pub fn get_raw_response_list(
+        db: Database,
+        txn: &RoTransaction,
+        key: QKey,
+        format: BinFormat,
+    ) -> Result<RawResponseList, Error> {
+        let mut key_buf = [0; 4];
+        LittleEndian::write_u32(&mut key_buf, key);
+        let data = txn.get(db, &key_buf)?;
+        Ok(parse_raw_response_list((&key_buf[..], data), format)?)
+    }
+
     /// Retrieve server responses for all queries.
+    ///
+    /// Each response list must contain a reply from each of the `servers` stored in meta db.
     pub fn get_response_lists(
         db: Database,
         txn: &RoTransaction,
+        format: BinFormat,
+        servers: usize,
     ) -> Result<Vec<ServerResponseList>, Error> {
         let mut cur = txn.open_ro_cursor(db)?;
         let mut lists: Vec<_> = Vec::new();
 
         for res in cur.iter() {
-            lists.push(ServerResponseList::try_from(res?)?);
+            let list = parse_response_list(res?, format)?;
+            if list.replies.len() != servers {
+                return Err(DbFormatError::ReplyCountMismatch {
+                    key: list.key,
+                    expected: servers,
+                    found: list.replies.len(),
+                }
+                .into());
+            }
+            lists.push(list);
         }
         Ok(lists)
     }
@@ -280,7 +571,25 @@ pub mod answersdb {
         }
     }
 
-    /// Serialize RawResponseList into binary data.
+    /// Serialize RawResponseList into binary data in the given format.
+    ///
+    /// Responses without details are stored with default `ResponseMeta` in format v2.
+    pub fn serialize_response_list(list: RawResponseList, format: BinFormat) -> Vec<u8> {
+        if format == BinFormat::V1 {
+            return list.into();
+        }
+        let mut data = Vec::new();
+        for (i, response) in list.responses.into_iter().enumerate() {
+            let meta = list.meta.get(i).copied().unwrap_or_default();
+            let mut response: Vec<u8> = response.into();
+            data.extend_from_slice(&response[..HEADER_LEN_V1]);
+            data.extend_from_slice(&serialize_meta(&meta));
+            data.extend(response.drain(HEADER_LEN_V1..));
+        }
+        data
+    }
+
+    /// Serialize RawResponseList into binary data (format v1).
     impl From<RawResponseList> for Vec<u8> {
         fn from(value: RawResponseList) -> Vec<u8> {
             let mut data = Vec::new();
@@ -296,8 +605,7 @@ pub mod answersdb {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::error::DbFormatError;
-    use lmdb::{Error as LmdbError, Transaction};
+    use lmdb::{Transaction, WriteFlags};
     use tempdir::TempDir;
 
     #[test]
@@ -307,12 +615,179 @@ mod tests {
         let db = open_db(&env, metadb::NAME, true).unwrap();
 
         let mut txn = env.begin_rw_txn().unwrap();
-        metadb::write_version(db, &mut txn).unwrap();
+        metadb::write_version(db, &mut txn, BinFormat::DEFAULT).unwrap();
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        let version = metadb::check_version(db, &txn).unwrap();
+        assert_eq!(version, BinFormat::V1);
+        drop(txn);
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        metadb::write_version(db, &mut txn, BinFormat::V2).unwrap();
         txn.commit().unwrap();
 
         let txn = env.begin_ro_txn().unwrap();
         let version = metadb::check_version(db, &txn).unwrap();
-        assert_eq!(version, BIN_FORMAT_VERSION);
+        assert_eq!(version, BinFormat::V2);
+    }
+
+    #[test]
+    fn metadb_servers() {
+        let dir = TempDir::new("test").unwrap();
+        let env = open_env(dir.path()).unwrap();
+        let db = open_db(&env, metadb::NAME, true).unwrap();
+
+        let servers = vec!["kresd".to_string(), "unbound".to_string()];
+        let mut txn = env.begin_rw_txn().unwrap();
+        metadb::write_version(db, &mut txn, BinFormat::DEFAULT).unwrap();
+        metadb::write_servers(db, &mut txn, servers.clone()).unwrap();
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(metadb::read_servers(db, &txn).unwrap(), servers);
+        assert_eq!(
+            metadb::read_version(db, &txn).unwrap(),
+            BinFormat::DEFAULT.version()
+        );
+    }
+
+    #[test]
+    fn metadb_truncated() {
+        let dir = TempDir::new("test").unwrap();
+        let env = open_env(dir.path()).unwrap();
+        let db = open_db(&env, metadb::NAME, true).unwrap();
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(db, b"servers", &[2, 0], WriteFlags::empty())
+            .unwrap();
+        txn.put(db, b"start_time", &[1], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(
+            metadb::read_servers(db, &txn),
+            Err(DbFormatError::InvalidMetadata("servers".to_string()).into())
+        );
+        assert_eq!(
+            metadb::read_start_time(db, &txn),
+            Err(DbFormatError::InvalidMetadata("start_time".to_string()).into())
+        );
+    }
+
+    #[test]
+    fn env_check() {
+        let dir = TempDir::new("test").unwrap();
+        let env = open_env(dir.path()).unwrap();
+        let servers = vec!["kresd".to_string(), "unbound".to_string()];
+
+        assert_eq!(
+            check_env(&env, &servers),
+            Err(DbFormatError::MissingMetadata("meta".to_string()).into())
+        );
+
+        let db = open_db(&env, metadb::NAME, true).unwrap();
+        let mut txn = env.begin_rw_txn().unwrap();
+        metadb::write_servers(db, &mut txn, servers.clone()).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            check_env(&env, &servers),
+            Err(DbFormatError::MissingMetadata("version".to_string()).into())
+        );
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(db, b"version", b"2000-01-01", WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            check_env(&env, &servers),
+            Err(DbFormatError::Unsupported {
+                found: "2000-01-01".to_string(),
+                expected: "2018-05-21, 2026-10-18".to_string(),
+            }
+            .into())
+        );
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        metadb::write_version(db, &mut txn, BinFormat::DEFAULT).unwrap();
+        txn.commit().unwrap();
+        assert_eq!(
+            check_env(&env, &servers),
+            Ok(EnvInfo {
+                format: BinFormat::DEFAULT,
+                order: vec![0, 1],
+            })
+        );
+
+        let reordered = vec!["unbound".to_string(), "kresd".to_string()];
+        assert_eq!(check_env(&env, &reordered).unwrap().order, vec![1, 0]);
+
+        let duplicate = vec!["kresd".to_string(), "kresd".to_string()];
+        assert!(check_env(&env, &duplicate).is_err());
+
+        let configured = vec!["kresd".to_string(), "bind".to_string()];
+        assert_eq!(
+            check_env(&env, &configured),
+            Err(DbFormatError::ServersMismatch {
+                stored: servers,
+                configured,
+            }
+            .into())
+        );
+    }
+
+    #[test]
+    fn grow_map() {
+        let dir = TempDir::new("test").unwrap();
+        let config = LmdbConfig {
+            map_size: 64 * 1024,
+            ..Default::default()
+        };
+        let env = open_env_with_config(dir.path(), &config).unwrap();
+        let db = open_db(&env, "d1", true).unwrap();
+
+        let items: Vec<_> = (0u32..64)
+            .map(|i| (i.to_le_bytes(), vec![0u8; 4096]))
+            .collect();
+        write_batch(&env, db, &items).unwrap();
+        assert!(env.info().unwrap().map_size() > config.map_size);
+
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(txn.get(db, &63u32.to_le_bytes()).unwrap().len(), 4096);
+    }
+
+    #[test]
+    fn reply_count() {
+        let dir = TempDir::new("test").unwrap();
+        let env = open_env(dir.path()).unwrap();
+        let adb = open_db(&env, answersdb::NAME, true).unwrap();
+        let timeout = [0xff, 0xff, 0xff, 0xff, 0x00, 0x00];
+        let items = [
+            (1u32.to_le_bytes(), [timeout, timeout].concat()),
+            (2u32.to_le_bytes(), timeout.to_vec()),
+        ];
+        write_batch(&env, adb, &items).unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(
+            answersdb::get_response_lists(adb, &txn, BinFormat::V1, 2),
+            Err(DbFormatError::ReplyCountMismatch {
+                key: 2,
+                expected: 2,
+                found: 1
+            }
+            .into())
+        );
+        assert_eq!(
+            answersdb::get_response_lists(adb, &txn, BinFormat::V1, 1),
+            Err(DbFormatError::ReplyCountMismatch {
+                key: 1,
+                expected: 1,
+                found: 2
+            }
+            .into())
+        );
     }
 
     #[test]
@@ -321,8 +796,8 @@ mod tests {
         let env = open_env(dir.path()).unwrap();
         let _d1 = open_db(&env, "d1", true).unwrap();
 
-        assert_eq!(exists_db(&env, "d1").unwrap(), true);
-        assert_eq!(exists_db(&env, "x").unwrap(), false);
+        assert!(exists_db(&env, "d1").unwrap());
+        assert!(!exists_db(&env, "x").unwrap());
 
         // trigger DbsFull becuase we set db limit to 5
         let _d2 = open_db(&env, "d2", true).unwrap();
@@ -348,7 +823,8 @@ mod tests {
             ServerResponseList::try_from((key.as_slice(), empty.as_slice())),
             Ok(ServerResponseList {
                 key: 0x42,
-                replies: vec![]
+                replies: vec![],
+                meta: vec![],
             })
         );
 
@@ -358,13 +834,14 @@ mod tests {
             Ok(ServerResponseList {
                 key: 0x42,
                 replies: vec![ServerResponse::Timeout,],
+                meta: vec![],
             })
         );
 
         let missingdata = vec![0x00, 0x00, 0x00, 0x00, 0x01, 0x00];
         assert_eq!(
             ServerResponseList::try_from((key.as_slice(), missingdata.as_slice())),
-            Err(DbFormatError::ReplyMissingData.into())
+            Err(DbFormatError::ReplyMissingData)
         );
 
         let shortdata = vec![0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00];
@@ -373,6 +850,7 @@ mod tests {
             Ok(ServerResponseList {
                 key: 0x42,
                 replies: vec![ServerResponse::Malformed],
+                meta: vec![],
             })
         );
 
@@ -391,6 +869,7 @@ mod tests {
             Ok(ServerResponseList {
                 key: 0x42,
                 replies: vec![ServerResponse::Data(dnsreply.to_owned())],
+                meta: vec![],
             })
         );
 
@@ -410,7 +889,71 @@ mod tests {
                         message: Message::from_octets(wire.to_owned()).unwrap(),
                     }),
                 ],
+                meta: vec![],
             })
         );
     }
+
+    #[test]
+    fn answers_format_v2() {
+        use crate::transceive::{RawResponse, RawResponseList};
+        use crate::{config::TransportProtocol, FailureKind, ResponseMeta, ServerResponse};
+        use std::time::Duration;
+
+        let wire = vec![
+            0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c,
+        ];
+        let meta = vec![
+            ResponseMeta {
+                transport: TransportProtocol::Tcp,
+                sent_at: 1_700_000_000_000_000,
+                retries: 2,
+                tcp_fallback: true,
+                failure: None,
+            },
+            ResponseMeta {
+                failure: Some(FailureKind::Refused),
+                ..Default::default()
+            },
+        ];
+        let list = RawResponseList {
+            key: 0x42,
+            responses: vec![
+                RawResponse::Data {
+                    delay: Duration::from_micros(7),
+                    wire: wire.clone(),
+                },
+                RawResponse::Timeout,
+            ],
+            meta: meta.clone(),
+        };
+        let key = [0x42, 0x00, 0x00, 0x00];
+
+        let data = answersdb::serialize_response_list(list.clone(), BinFormat::V2);
+        assert_eq!(data.len(), 2 * 18 + wire.len());
+        let parsed = answersdb::parse_response_list((&key, &data), BinFormat::V2).unwrap();
+        assert_eq!(parsed.meta, meta);
+        assert_eq!(parsed.replies[1], ServerResponse::Timeout);
+        assert!(matches!(&parsed.replies[0], ServerResponse::Data(r) if r.delay.as_micros() == 7));
+
+        // v1 data doesn't carry any details
+        let data = answersdb::serialize_response_list(list, BinFormat::V1);
+        let parsed = answersdb::parse_response_list((&key, &data), BinFormat::V1).unwrap();
+        assert!(parsed.meta.is_empty());
+        assert_eq!(parsed.replies.len(), 2);
+
+        let mut invalid = answersdb::serialize_response_list(
+            RawResponseList {
+                key: 0x42,
+                responses: vec![RawResponse::Timeout],
+                meta: vec![],
+            },
+            BinFormat::V2,
+        );
+        invalid[14] = 9; // transport
+        assert_eq!(
+            answersdb::parse_response_list((&key, &invalid), BinFormat::V2),
+            Err(DbFormatError::ReplyInvalidData)
+        );
+    }
 }
@@ -1,27 +1,84 @@
 use crate::{
-    matcher::{Field, FieldMismatches},
+    config::{Config, FieldWeight},
+    error::Error,
+    matcher::{Field, FieldMismatches, Mismatch},
     QKey,
 };
-use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, BTreeSet};
+use domain::base::{question::Question, ToDname};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+/// Version of the JSON datafile format written by this respdiff.
+///
+/// Datafiles without a version were written by Python respdiff or an older respdiff-rs.
+pub const REPORT_VERSION: u32 = 1;
 
 /// JSON datafile report
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct Report {
+    #[serde(default)]
+    pub version: u32,
     pub start_time: u32,
     pub end_time: u32,
     pub total_queries: u64,
     pub total_answers: u64,
     other_disagreements: OtherDisagreements,
     target_disagreements: TargetDisagreements,
-    pub summary: Option<()>,
-    pub reprodata: Option<()>,
+    pub summary: Option<Summary>,
+    pub reprodata: Option<ReproData>,
+    /// Decoded questions of (a sample of) queries with target disagreements.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub questions: BTreeMap<QKey, QueryInfo>,
+    /// Information about how the report was produced.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
 }
 
 impl Report {
     /// Create new Report
     pub fn new() -> Self {
-        Default::default()
+        Report {
+            version: REPORT_VERSION,
+            ..Default::default()
+        }
+    }
+
+    /// Load a report from a JSON datafile, see `from_reader()`.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let file = File::open(path).map_err(Error::DatafileOpen)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Load a report from JSON, checking its version.
+    ///
+    /// Unversioned reports are migrated to the current version.
+    pub fn from_reader(reader: impl Read) -> Result<Self, Error> {
+        let mut value: Value = serde_json::from_reader(reader).map_err(Error::DatafileRead)?;
+        match value.get("version").map(|v| (v.as_u64(), v)) {
+            None => {
+                let python = is_python(&value);
+                migrate_legacy(&mut value);
+                let mut report: Report = serde_json::from_value(value).map_err(|e| {
+                    if python {
+                        Error::DatafilePython(e)
+                    } else {
+                        Error::DatafileLegacy(e)
+                    }
+                })?;
+                report.version = REPORT_VERSION;
+                Ok(report)
+            }
+            Some((Some(version), _)) if version <= REPORT_VERSION as u64 => {
+                serde_json::from_value(value).map_err(Error::DatafileRead)
+            }
+            Some((Some(version), _)) => Err(Error::DatafileVersion(version, REPORT_VERSION)),
+            Some((None, version)) => Err(Error::DatafileVersionInvalid(version.to_string())),
+        }
     }
 
     /// Return a set of queries on which other servers (besides target) disagree
@@ -35,7 +92,196 @@ impl Report {
         self.other_disagreements.queries = queries.clone();
     }
 
-    // FIXME: no way to retrieve target_disagrees - not needed right now
+    /// Return keys of all queries recorded in the report.
+    fn keys(&self) -> BTreeSet<QKey> {
+        let mut keys = self.target_disagreeing_queries();
+        keys.extend(&self.other_disagreements.queries);
+        keys.extend(self.reprodata.iter().flat_map(|repro| repro.keys()));
+        keys.extend(self.questions.keys());
+        keys
+    }
+
+    /// Return a set of queries on which target disagrees with others.
+    pub fn target_disagreeing_queries(&self) -> BTreeSet<QKey> {
+        self.target_disagreements
+            .fields
+            .values()
+            .flat_map(|fdis| fdis.queries())
+            .collect()
+    }
+
+    /// Return target disagreements for each field.
+    pub fn target_disagreements(&self) -> &BTreeMap<Field, FieldDisagreements> {
+        &self.target_disagreements.fields
+    }
+
+    /// Return a collection of target mismatches for each field.
+    ///
+    /// This is the inverse of `set_target_disagrees()`. Mismatches are reconstructed from their
+    /// string values, which fails if the report contains values that can't be parsed.
+    pub fn target_disagrees(&self) -> Result<BTreeMap<Field, FieldMismatches>, Error> {
+        let mut dis = BTreeMap::new();
+        for (field, fdis) in &self.target_disagreements.fields {
+            let mut fmismatches = FieldMismatches::new();
+            for mmqueries in &fdis.mismatches {
+                fmismatches
+                    .entry(mmqueries.mismatch(*field)?)
+                    .or_default()
+                    .extend(mmqueries.queries.iter().copied());
+            }
+            dis.insert(*field, fmismatches);
+        }
+        Ok(dis)
+    }
+
+    /// Return a sample of queries with target disagreements.
+    ///
+    /// At most `limit` queries are taken from each mismatch. All queries are returned if
+    /// `limit` is `None`.
+    pub fn sample_queries(&self, limit: Option<usize>) -> BTreeSet<QKey> {
+        self.target_disagreements
+            .fields
+            .values()
+            .flat_map(|fdis| fdis.mismatches.iter())
+            .flat_map(|mmqueries| {
+                mmqueries
+                    .queries
+                    .iter()
+                    .take(limit.unwrap_or(usize::MAX))
+                    .copied()
+            })
+            .collect()
+    }
+
+    /// Return target mismatches of a single query.
+    pub fn query_mismatches(&self, key: QKey) -> Vec<(Field, &MismatchQueries)> {
+        self.target_disagreements
+            .fields
+            .iter()
+            .flat_map(|(field, fdis)| {
+                fdis.mismatches
+                    .iter()
+                    .filter(move |mmqueries| mmqueries.queries.contains(&key))
+                    .map(move |mmqueries| (*field, mmqueries))
+            })
+            .collect()
+    }
+
+    /// Record the result of an attempt to reproduce the target disagreement for a query.
+    ///
+    /// `mismatches` are the target mismatches that were found in the new answers. They're only
+    /// taken into account when others agree.
+    pub fn add_repro_result(
+        &mut self,
+        key: QKey,
+        others_agree: bool,
+        mismatches: &HashSet<Mismatch>,
+    ) {
+        let reproduced = others_agree && {
+            let expected: BTreeSet<_> = self
+                .query_mismatches(key)
+                .into_iter()
+                .map(|(field, mmqueries)| {
+                    (field, mmqueries.exp_val.clone(), mmqueries.got_val.clone())
+                })
+                .collect();
+            let got: BTreeSet<_> = mismatches
+                .iter()
+                .map(|mismatch| (Field::from(mismatch), mismatch.expected(), mismatch.got()))
+                .collect();
+            expected == got
+        };
+
+        let counter = self
+            .reprodata
+            .get_or_insert_with(ReproData::new)
+            .entry(key)
+            .or_default();
+        counter.retries += 1;
+        if others_agree {
+            counter.upstream_stable += 1;
+            if reproduced {
+                counter.verified += 1;
+            } else if !mismatches.is_empty() {
+                counter.different_failure += 1;
+            }
+        }
+    }
+
+    /// Merge another report, e.g. from a different shard of the same query set, into this one.
+    ///
+    /// Both reports must have provenance with the same servers, target and criteria, and their
+    /// queries must have distinct keys. The summary is dropped, since it no longer matches the
+    /// merged data.
+    pub fn merge(&mut self, other: &Report) -> Result<(), Error> {
+        let incompatible = |reason: &str| Err(Error::IncompatibleReports(reason.to_string()));
+        match (&self.provenance, &other.provenance) {
+            (Some(a), Some(b)) => {
+                if a.sorted_servers() != b.sorted_servers() {
+                    return incompatible("server sets differ");
+                }
+                if a.target != b.target {
+                    return incompatible("target servers differ");
+                }
+                if a.criteria != b.criteria {
+                    return incompatible("diff criteria differ");
+                }
+            }
+            _ => return incompatible("report without provenance"),
+        }
+        if let Some(key) = self.keys().intersection(&other.keys()).next() {
+            return incompatible(&format!("query key {} is present in both reports", key));
+        }
+
+        self.start_time = self.start_time.min(other.start_time);
+        self.end_time = self.end_time.max(other.end_time);
+        self.total_queries += other.total_queries;
+        self.total_answers += other.total_answers;
+        self.other_disagreements
+            .queries
+            .extend(&other.other_disagreements.queries);
+
+        for (field, fdis) in &other.target_disagreements.fields {
+            let mismatches = &mut self
+                .target_disagreements
+                .fields
+                .entry(*field)
+                .or_default()
+                .mismatches;
+            for mmqueries in &fdis.mismatches {
+                match mismatches
+                    .iter_mut()
+                    .find(|m| m.exp_val == mmqueries.exp_val && m.got_val == mmqueries.got_val)
+                {
+                    Some(existing) => {
+                        let queries: BTreeSet<QKey> = existing
+                            .queries
+                            .iter()
+                            .chain(&mmqueries.queries)
+                            .copied()
+                            .collect();
+                        existing.queries = queries.into_iter().collect();
+                    }
+                    None => mismatches.push(mmqueries.clone()),
+                }
+            }
+        }
+
+        if let Some(other_repro) = &other.reprodata {
+            let reprodata = self.reprodata.get_or_insert_with(ReproData::new);
+            for (key, counter) in other_repro {
+                let merged = reprodata.entry(*key).or_default();
+                merged.retries += counter.retries;
+                merged.upstream_stable += counter.upstream_stable;
+                merged.verified += counter.verified;
+                merged.different_failure += counter.different_failure;
+            }
+        }
+        self.questions
+            .extend(other.questions.iter().map(|(k, v)| (*k, v.clone())));
+        self.summary = None;
+        Ok(())
+    }
 
     /// Return a collection of target mismatches for each field.
     pub fn set_target_disagrees(&mut self, dis: BTreeMap<Field, FieldMismatches>) {
@@ -67,11 +313,35 @@ struct TargetDisagreements {
     fields: BTreeMap<Field, FieldDisagreements>,
 }
 
+/// Collection of mismatches within a single field.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
-struct FieldDisagreements {
+pub struct FieldDisagreements {
     mismatches: Vec<MismatchQueries>,
 }
 
+impl FieldDisagreements {
+    /// Return all mismatches in this field.
+    pub fn mismatches(&self) -> &[MismatchQueries] {
+        &self.mismatches
+    }
+
+    /// Return the number of queries with a mismatch in this field.
+    pub fn count(&self) -> u64 {
+        self.mismatches
+            .iter()
+            .map(|mmqueries| mmqueries.queries.len() as u64)
+            .sum()
+    }
+
+    /// Return a set of all queries with a mismatch in this field.
+    pub fn queries(&self) -> BTreeSet<QKey> {
+        self.mismatches
+            .iter()
+            .flat_map(|mmqueries| mmqueries.queries.iter().copied())
+            .collect()
+    }
+}
+
 /// Collection of queries that share the same mismatch.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
 pub struct MismatchQueries {
@@ -80,11 +350,372 @@ pub struct MismatchQueries {
     pub queries: Vec<QKey>,
 }
 
+impl MismatchQueries {
+    /// Reconstruct the mismatch from its string values.
+    pub fn mismatch(&self, field: Field) -> Result<Mismatch, Error> {
+        Mismatch::parse(field, &self.exp_val, &self.got_val)
+    }
+}
+
+/// Decoded question of a query in presentation format.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct QueryInfo {
+    pub qname: String,
+    pub qtype: String,
+    pub qclass: String,
+}
+
+impl<N: ToDname + fmt::Display> From<&Question<N>> for QueryInfo {
+    fn from(question: &Question<N>) -> Self {
+        QueryInfo {
+            qname: format!("{}.", question.qname()),
+            qtype: question.qtype().to_string(),
+            qclass: question.qclass().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for QueryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.qname, self.qtype, self.qclass)
+    }
+}
+
+/// Information needed to reproduce and audit a run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Provenance {
+    /// Version of respdiff which produced the report.
+    pub respdiff_version: String,
+    /// Binary format version of LMDB the report was produced from.
+    pub format_version: Option<String>,
+    /// Server list stored in the meta LMDB.
+    pub db_servers: Vec<String>,
+    /// Servers from the configuration, in the configured order.
+    pub servers: Vec<ServerInfo>,
+    /// Name of the target server.
+    pub target: String,
+    /// Diff criteria used to compare answers.
+    pub criteria: Vec<String>,
+    /// Field weights used to order fields in the summary.
+    pub field_weights: Vec<String>,
+}
+
+impl Provenance {
+    /// Record the configuration of the current respdiff version.
+    pub fn new(config: &Config) -> Self {
+        let servers = config
+            .servers
+            .iter()
+            .filter_map(|name| {
+                config.server_data.get(name).map(|data| ServerInfo {
+                    name: name.clone(),
+                    ip: data.ip.to_string(),
+                    port: data.port,
+                    transport: data.transport.to_string(),
+                })
+            })
+            .collect();
+        Provenance {
+            respdiff_version: env!("CARGO_PKG_VERSION").to_string(),
+            format_version: None,
+            db_servers: Vec::new(),
+            servers,
+            target: config.diff.target.clone(),
+            criteria: config.diff.criteria.iter().map(|c| c.to_string()).collect(),
+            field_weights: config
+                .report
+                .field_weights
+                .iter()
+                .map(|w| w.to_string())
+                .collect(),
+        }
+    }
+
+    /// Return the configured servers sorted by name.
+    pub fn sorted_servers(&self) -> Vec<&ServerInfo> {
+        let mut servers: Vec<_> = self.servers.iter().collect();
+        servers.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        servers
+    }
+}
+
+/// Server as configured for a run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    /// Name of the server section in the configuration.
+    pub name: String,
+    /// IP address queries were sent to.
+    pub ip: String,
+    /// Port queries were sent to.
+    pub port: u16,
+    /// Transport protocol: udp, tcp or tls.
+    pub transport: String,
+}
+
+/// Results of attempts to reproduce target disagreements for each query (diffrepro).
+pub type ReproData = BTreeMap<QKey, ReproCounter>;
+
+/// Counters of attempts to reproduce a target disagreement for a single query.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct ReproCounter {
+    /// Total number of attempts to reproduce.
+    pub retries: u64,
+    /// Number of attempts where other servers agreed.
+    pub upstream_stable: u64,
+    /// Number of attempts where the target disagreement was the same.
+    pub verified: u64,
+    /// Number of attempts where the target disagreed, but differently.
+    pub different_failure: u64,
+}
+
+impl ReproCounter {
+    /// Return the ratio of verified attempts, if there were any attempts.
+    pub fn reproducibility(&self) -> Option<f64> {
+        if self.retries == 0 {
+            return None;
+        }
+        Some(self.verified as f64 / self.retries as f64)
+    }
+}
+
+/// Summary of target disagreements (diffsum).
+///
+/// Unlike target disagreements, each query is counted at most once -- under the field with the
+/// highest weight.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct Summary {
+    /// Number of queries on which other servers disagree.
+    pub upstream_unstable: u64,
+    /// Number of answers that are usable for comparison.
+    pub usable_answers: u64,
+    /// Number of queries whose mismatches couldn't be reproduced.
+    pub not_reproducible: u64,
+    /// Number of queries that were ignored on request (kept for compatibility with Python
+    /// respdiff).
+    pub manual_ignore: u64,
+    #[serde(serialize_with = "serialize_counted_fields")]
+    fields: BTreeMap<Field, FieldDisagreements>,
+}
+
+impl Summary {
+    /// Create a summary from the report's target disagreements.
+    ///
+    /// Queries with multiple mismatches are counted only once, under the field which comes
+    /// first in `field_weights`. Fields which aren't listed in `field_weights` are ignored.
+    ///
+    /// If `reproducibility_threshold` is set and the report contains reprodata, queries whose
+    /// upstream wasn't stable during reproduction are counted as upstream unstable and queries
+    /// with reproducibility below the threshold are counted as not reproducible.
+    pub fn from_report(
+        report: &Report,
+        field_weights: &[FieldWeight],
+        reproducibility_threshold: Option<f64>,
+    ) -> Self {
+        let mut upstream_unstable = report.other_disagreements.queries.len() as u64;
+        let mut not_reproducible = 0;
+
+        // queries filtered out based on reprodata
+        let mut filtered: BTreeSet<QKey> = BTreeSet::new();
+        if let (Some(threshold), Some(reprodata)) = (reproducibility_threshold, &report.reprodata) {
+            for qkey in report.target_disagreeing_queries() {
+                let counter = match reprodata.get(&qkey) {
+                    Some(counter) if counter.retries > 0 => counter,
+                    _ => continue,
+                };
+                if counter.retries != counter.upstream_stable {
+                    upstream_unstable += 1;
+                    filtered.insert(qkey);
+                } else if counter.reproducibility().unwrap_or(0.0) < threshold {
+                    not_reproducible += 1;
+                    filtered.insert(qkey);
+                }
+            }
+        }
+
+        let weight = |field: &Field| {
+            field_weights
+                .iter()
+                .position(|fw| *fw == FieldWeight::from(*field))
+        };
+
+        // find the field with the highest weight (lowest position) for each query
+        let mut selected: BTreeMap<QKey, (usize, Field, usize)> = BTreeMap::new();
+        for (field, fdis) in &report.target_disagreements.fields {
+            let w = match weight(field) {
+                Some(w) => w,
+                None => continue,
+            };
+            for (i, mmqueries) in fdis.mismatches.iter().enumerate() {
+                for qkey in &mmqueries.queries {
+                    if filtered.contains(qkey) {
+                        continue;
+                    }
+                    match selected.get(qkey) {
+                        Some((sw, _, _)) if *sw <= w => {}
+                        _ => {
+                            selected.insert(*qkey, (w, *field, i));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut fields: BTreeMap<Field, FieldDisagreements> = BTreeMap::new();
+        for (field, fdis) in &report.target_disagreements.fields {
+            let mismatches: Vec<MismatchQueries> = fdis
+                .mismatches
+                .iter()
+                .enumerate()
+                .filter_map(|(i, mmqueries)| {
+                    let queries: Vec<QKey> = mmqueries
+                        .queries
+                        .iter()
+                        .copied()
+                        .filter(|qkey| match selected.get(qkey) {
+                            Some((_, sfield, si)) => sfield == field && *si == i,
+                            None => false,
+                        })
+                        .collect();
+                    if queries.is_empty() {
+                        return None;
+                    }
+                    Some(MismatchQueries {
+                        exp_val: mmqueries.exp_val.clone(),
+                        got_val: mmqueries.got_val.clone(),
+                        queries,
+                    })
+                })
+                .collect();
+            if !mismatches.is_empty() {
+                fields.insert(*field, FieldDisagreements { mismatches });
+            }
+        }
+
+        Summary {
+            upstream_unstable,
+            usable_answers: report
+                .total_answers
+                .saturating_sub(upstream_unstable)
+                .saturating_sub(not_reproducible),
+            not_reproducible,
+            manual_ignore: 0,
+            fields,
+        }
+    }
+
+    /// Return disagreements for each field.
+    pub fn fields(&self) -> &BTreeMap<Field, FieldDisagreements> {
+        &self.fields
+    }
+
+    /// Return the number of queries counted in the summary.
+    pub fn total(&self) -> u64 {
+        self.fields.values().map(|fdis| fdis.count()).sum()
+    }
+
+    /// Return the number of queries counted under the given field.
+    pub fn field_count(&self, field: Field) -> u64 {
+        self.fields.get(&field).map_or(0, |fdis| fdis.count())
+    }
+}
+
+/// Convert an unversioned report to the shape of the current version.
+///
+/// Python respdiff stores some mismatch values as lists, which are joined into the
+/// space-separated form used here.
+/// Return whether an unversioned report was written by Python respdiff.
+///
+/// Only Python respdiff stores mismatch values as lists and reproduction counters as arrays.
+fn is_python(report: &Value) -> bool {
+    let list_values = ["target_disagreements", "summary"]
+        .iter()
+        .filter_map(|key| report.get(key)?.get("fields")?.as_object())
+        .flat_map(|fields| fields.values())
+        .filter_map(|fdis| fdis.get("mismatches")?.as_array())
+        .flatten()
+        .any(|mismatch| {
+            ["exp_val", "got_val"]
+                .iter()
+                .any(|key| mismatch.get(key).is_some_and(Value::is_array))
+        });
+    let array_counters = report
+        .get("reprodata")
+        .and_then(Value::as_object)
+        .is_some_and(|repro| repro.values().any(Value::is_array));
+    list_values || array_counters
+}
+
+fn migrate_legacy(report: &mut Value) {
+    for key in ["target_disagreements", "summary"] {
+        let fields = match report
+            .get_mut(key)
+            .and_then(|section| section.get_mut("fields"))
+            .and_then(Value::as_object_mut)
+        {
+            Some(fields) => fields,
+            None => continue,
+        };
+        for fdis in fields.values_mut() {
+            let mismatches = match fdis.get_mut("mismatches").and_then(Value::as_array_mut) {
+                Some(mismatches) => mismatches,
+                None => continue,
+            };
+            for mismatch in mismatches.iter_mut().filter_map(Value::as_object_mut) {
+                for key in ["exp_val", "got_val"] {
+                    if let Some(Value::Array(items)) = mismatch.get(key) {
+                        let joined = items
+                            .iter()
+                            .map(|item| match item {
+                                Value::String(s) => s.clone(),
+                                other => other.to_string(),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        mismatch.insert(key.to_string(), Value::String(joined));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Serialize fields including the counts, in the same shape as Python respdiff does.
+fn serialize_counted_fields<S>(
+    fields: &BTreeMap<Field, FieldDisagreements>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    struct CountedMismatch<'a>(&'a MismatchQueries);
+    impl Serialize for CountedMismatch<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut s = serializer.serialize_struct("MismatchQueries", 4)?;
+            s.serialize_field("count", &self.0.queries.len())?;
+            s.serialize_field("exp_val", &self.0.exp_val)?;
+            s.serialize_field("got_val", &self.0.got_val)?;
+            s.serialize_field("queries", &self.0.queries)?;
+            s.end()
+        }
+    }
+    struct CountedField<'a>(&'a FieldDisagreements);
+    impl Serialize for CountedField<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mismatches: Vec<_> = self.0.mismatches.iter().map(CountedMismatch).collect();
+            let mut s = serializer.serialize_struct("FieldDisagreements", 2)?;
+            s.serialize_field("count", &mismatches.len())?;
+            s.serialize_field("mismatches", &mismatches)?;
+            s.end()
+        }
+    }
+    serializer.collect_map(fields.iter().map(|(k, v)| (k, CountedField(v))))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    const JSON_FORMAT: &'static str = r#"
+    const JSON_FORMAT: &str = r#"
 {
   "start_time": 1628173617,
   "end_time": 1628174644,
@@ -127,6 +758,7 @@ mod tests {
 
     fn expected() -> Report {
         Report {
+            version: 0,
             start_time: 1628173617,
             end_time: 1628174644,
             total_queries: 100,
@@ -170,6 +802,8 @@ mod tests {
             },
             summary: None,
             reprodata: None,
+            questions: BTreeMap::new(),
+            provenance: None,
         }
     }
 
@@ -181,4 +815,344 @@ mod tests {
         let deser = serde_json::from_str::<Report>(&ser).unwrap();
         assert_eq!(expected(), deser);
     }
+
+    #[test]
+    fn summary_weights() {
+        let mut report = expected();
+        // query 32 has both rcode and flags mismatch
+        report.target_disagreements.fields.insert(
+            Field::Flags,
+            FieldDisagreements {
+                mismatches: vec![MismatchQueries {
+                    exp_val: "QR RD RA AD".to_string(),
+                    got_val: "QR RD RA".to_string(),
+                    queries: vec![6, 32, 33, 46, 85],
+                }],
+            },
+        );
+
+        let summary =
+            Summary::from_report(&report, &[FieldWeight::Rcode, FieldWeight::Flags], None);
+        assert_eq!(summary.upstream_unstable, 3);
+        assert_eq!(summary.usable_answers, 96);
+        assert_eq!(summary.field_count(Field::Rcode), 3);
+        assert_eq!(summary.field_count(Field::Flags), 4);
+        assert_eq!(summary.total(), 7);
+
+        let summary =
+            Summary::from_report(&report, &[FieldWeight::Flags, FieldWeight::Rcode], None);
+        assert_eq!(summary.field_count(Field::Rcode), 2);
+        assert_eq!(summary.field_count(Field::Flags), 5);
+        assert_eq!(
+            summary.fields[&Field::Rcode].mismatches[0].queries,
+            vec![16]
+        );
+
+        let summary = Summary::from_report(&report, &[FieldWeight::Flags], None);
+        assert_eq!(summary.field_count(Field::Rcode), 0);
+        assert_eq!(summary.total(), 5);
+    }
+
+    #[test]
+    fn summary_serde() {
+        let summary =
+            Summary::from_report(&expected(), &[FieldWeight::Rcode, FieldWeight::Flags], None);
+        let ser = serde_json::to_value(&summary).unwrap();
+        assert_eq!(ser["upstream_unstable"], 3);
+        assert_eq!(ser["fields"]["rcode"]["count"], 2);
+        assert_eq!(ser["fields"]["rcode"]["mismatches"][0]["count"], 2);
+        assert_eq!(ser["fields"]["flags"]["mismatches"][0]["count"], 4);
+        let deser = serde_json::from_value::<Summary>(ser).unwrap();
+        assert_eq!(summary, deser);
+    }
+
+    #[test]
+    fn reprodata() {
+        use domain::base::iana::rcode::Rcode;
+
+        let mut report = expected();
+        let rcode: HashSet<_> = [Mismatch::Rcode(Rcode::NoError, Rcode::ServFail)]
+            .into_iter()
+            .collect();
+        report.add_repro_result(6, true, &rcode);
+        report.add_repro_result(16, true, &HashSet::new());
+        report.add_repro_result(43, false, &HashSet::new());
+        report.add_repro_result(32, true, &rcode);
+
+        let reprodata = report.reprodata.as_ref().unwrap();
+        assert_eq!(
+            reprodata[&6],
+            ReproCounter {
+                retries: 1,
+                upstream_stable: 1,
+                verified: 1,
+                different_failure: 0,
+            }
+        );
+        assert_eq!(reprodata[&16].verified, 0);
+        assert_eq!(reprodata[&43].upstream_stable, 0);
+        assert_eq!(reprodata[&32].different_failure, 1);
+
+        let ser = serde_json::to_value(&report).unwrap();
+        assert_eq!(ser["reprodata"]["6"]["verified"], 1);
+        let deser = serde_json::from_value::<Report>(ser).unwrap();
+        assert_eq!(report, deser);
+
+        let weights = [FieldWeight::Rcode, FieldWeight::Flags];
+        let summary = Summary::from_report(&report, &weights, Some(1.0));
+        assert_eq!(summary.upstream_unstable, 4);
+        assert_eq!(summary.not_reproducible, 2);
+        assert_eq!(summary.usable_answers, 93);
+        assert_eq!(summary.field_count(Field::Rcode), 1);
+        assert_eq!(summary.field_count(Field::Flags), 3);
+
+        let summary = Summary::from_report(&report, &weights, None);
+        assert_eq!(summary.upstream_unstable, 3);
+        assert_eq!(summary.not_reproducible, 0);
+        assert_eq!(summary.total(), 7);
+    }
+
+    #[test]
+    fn target_disagreements_getters() {
+        use domain::base::iana::rcode::Rcode;
+
+        let report = expected();
+        let fields: Vec<_> = report.target_disagreements().keys().copied().collect();
+        assert_eq!(fields, vec![Field::Rcode, Field::Flags]);
+        assert_eq!(report.target_disagreements()[&Field::Rcode].count(), 3);
+        assert_eq!(
+            report.target_disagreements()[&Field::Flags].queries(),
+            [32, 33, 46, 85].iter().cloned().collect()
+        );
+        assert_eq!(
+            report.target_disagreeing_queries(),
+            [6, 16, 32, 33, 43, 46, 85].iter().cloned().collect()
+        );
+
+        let mismatches = report.query_mismatches(43);
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].0, Field::Rcode);
+        assert_eq!(
+            mismatches[0].1.mismatch(Field::Rcode),
+            Ok(Mismatch::Rcode(Rcode::NXDomain, Rcode::ServFail))
+        );
+
+        let dis = report.target_disagrees().unwrap();
+        let mut roundtrip = Report::new();
+        roundtrip.set_target_disagrees(dis.clone());
+        assert_eq!(roundtrip.target_disagrees().unwrap(), dis);
+        assert_eq!(
+            dis[&Field::Rcode][&Mismatch::Rcode(Rcode::NoError, Rcode::ServFail)],
+            [6, 16].iter().cloned().collect()
+        );
+    }
+
+    #[test]
+    fn questions() {
+        use domain::base::{iana::rtype::Rtype, name::Dname};
+
+        let mut report = expected();
+        assert_eq!(
+            report.sample_queries(Some(1)),
+            [6, 32, 43].iter().cloned().collect()
+        );
+        assert_eq!(
+            report.sample_queries(None),
+            report.target_disagreeing_queries()
+        );
+
+        let info = QueryInfo::from(&Question::new_in(
+            Dname::vec_from_str("example.com").unwrap(),
+            Rtype::Aaaa,
+        ));
+        assert_eq!(info.to_string(), "example.com. AAAA IN");
+        report.questions.insert(6, info);
+
+        let ser = serde_json::to_value(&report).unwrap();
+        assert_eq!(ser["questions"]["6"]["qname"], "example.com.");
+        let deser = serde_json::from_value::<Report>(ser).unwrap();
+        assert_eq!(report, deser);
+
+        // questions are omitted when empty
+        let ser = serde_json::to_value(expected()).unwrap();
+        assert!(ser.get("questions").is_none());
+    }
+
+    #[test]
+    fn provenance() {
+        let config = Config::try_from(&Some("test/msgdiff-json/respdiff.cfg".into())).unwrap();
+        let provenance = Provenance::new(&config);
+        assert_eq!(provenance.respdiff_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(provenance.target, config.diff.target);
+        assert_eq!(provenance.servers.len(), config.servers.len());
+        assert_eq!(provenance.servers[0].name, config.servers[0]);
+        assert_eq!(provenance.criteria.len(), config.diff.criteria.len());
+        assert_eq!(provenance.field_weights[0], "timeout");
+        assert_eq!(provenance.field_weights[12], "nsid");
+
+        let mut report = expected();
+        report.provenance = Some(provenance);
+        let ser = serde_json::to_string(&report).unwrap();
+        let deser = serde_json::from_str::<Report>(&ser).unwrap();
+        assert_eq!(report, deser);
+    }
+
+    #[test]
+    fn versioned_loading() {
+        // unversioned reports are migrated
+        let report = Report::from_reader(JSON_FORMAT.as_bytes()).unwrap();
+        assert_eq!(report.version, REPORT_VERSION);
+        assert_eq!(report.target_disagreements, expected().target_disagreements);
+
+        let ser = serde_json::to_string(&report).unwrap();
+        assert_eq!(Report::from_reader(ser.as_bytes()).unwrap(), report);
+
+        let legacy = r#"{
+            "start_time": 0, "end_time": 0, "total_queries": 1, "total_answers": 1,
+            "other_disagreements": {"queries": []},
+            "target_disagreements": {"fields": {"answertypes": {"mismatches": [
+                {"exp_val": ["A", "RRSIG"], "got_val": [], "queries": [1]}
+            ]}}},
+            "summary": null, "reprodata": null
+        }"#;
+        let report = Report::from_reader(legacy.as_bytes()).unwrap();
+        let mismatches = report.target_disagrees().unwrap();
+        let mismatch = mismatches[&Field::AnswerTypes].keys().next().unwrap();
+        assert_eq!(mismatch.expected(), "A RRSIG");
+
+        assert!(matches!(
+            Report::from_reader(r#"{"start_time": 0}"#.as_bytes()),
+            Err(Error::DatafileLegacy(_))
+        ));
+        assert!(matches!(
+            Report::from_reader(r#"{"version": 1, "start_time": 0}"#.as_bytes()),
+            Err(Error::DatafileRead(_))
+        ));
+        assert_eq!(
+            Report::from_reader(r#"{"version": 99}"#.as_bytes()),
+            Err(Error::DatafileVersion(99, REPORT_VERSION))
+        );
+        assert_eq!(
+            Report::from_reader(r#"{"version": "1"}"#.as_bytes()),
+            Err(Error::DatafileVersionInvalid(r#""1""#.to_string()))
+        );
+        assert!(matches!(
+            Report::from_reader("[".as_bytes()),
+            Err(Error::DatafileRead(_))
+        ));
+    }
+
+    #[test]
+    fn unversioned_origin() {
+        // Python respdiff stores mismatch values as lists and reproduction counters as arrays
+        let python_values = r#"{
+            "start_time": 0,
+            "target_disagreements": {"fields": {"answertypes": {"mismatches": [
+                {"exp_val": ["A"], "got_val": [], "queries": [1]}
+            ]}}}
+        }"#;
+        assert!(matches!(
+            Report::from_reader(python_values.as_bytes()),
+            Err(Error::DatafilePython(_))
+        ));
+        let python_repro = r#"{"start_time": 0, "reprodata": {"1": [1, 1, 1, 0]}}"#;
+        assert!(matches!(
+            Report::from_reader(python_repro.as_bytes()),
+            Err(Error::DatafilePython(_))
+        ));
+
+        let legacy = r#"{
+            "start_time": 0,
+            "target_disagreements": {"fields": {"answertypes": {"mismatches": [
+                {"exp_val": "A", "got_val": "", "queries": [1]}
+            ]}}},
+            "reprodata": {"1": {"retries": 1, "upstream_stable": 1, "verified": 1,
+                                "different_failure": 0}}
+        }"#;
+        assert!(matches!(
+            Report::from_reader(legacy.as_bytes()),
+            Err(Error::DatafileLegacy(_))
+        ));
+    }
+
+    #[test]
+    fn merge() {
+        let config = Config::try_from(&Some("test/msgdiff-json/respdiff.cfg".into())).unwrap();
+        let mut report = expected();
+        assert!(matches!(
+            report.clone().merge(&expected()),
+            Err(Error::IncompatibleReports(_))
+        ));
+        report.provenance = Some(Provenance::new(&config));
+
+        let mut other = Report::new();
+        other.start_time = 1628173000;
+        other.end_time = 1628175000;
+        other.total_queries = 10;
+        other.total_answers = 10;
+        other.set_others_disagree(&[23, 100].iter().cloned().collect());
+        other.target_disagreements.fields.insert(
+            Field::Rcode,
+            FieldDisagreements {
+                mismatches: vec![
+                    MismatchQueries {
+                        exp_val: "NOERROR".to_string(),
+                        got_val: "SERVFAIL".to_string(),
+                        queries: vec![7, 200],
+                    },
+                    MismatchQueries {
+                        exp_val: "NOERROR".to_string(),
+                        got_val: "REFUSED".to_string(),
+                        queries: vec![201],
+                    },
+                ],
+            },
+        );
+        other.provenance = Some(Provenance::new(&config));
+
+        let mut merged = report.clone();
+        merged.merge(&other).unwrap();
+        assert_eq!(merged.start_time, 1628173000);
+        assert_eq!(merged.end_time, 1628175000);
+        assert_eq!(merged.total_queries, 110);
+        assert_eq!(merged.total_answers, 109);
+        assert_eq!(
+            merged.others_disagree(),
+            [22, 23, 64, 93, 100].iter().cloned().collect()
+        );
+        let rcode = merged.target_disagreements()[&Field::Rcode].mismatches();
+        assert_eq!(rcode.len(), 3);
+        assert_eq!(rcode[0].queries, vec![6, 7, 16, 200]);
+        assert_eq!(rcode[2].queries, vec![201]);
+        assert_eq!(
+            merged.target_disagreements()[&Field::Flags],
+            report.target_disagreements()[&Field::Flags]
+        );
+
+        let mut incompatible = other.clone();
+        incompatible.provenance.as_mut().unwrap().target = "bind".to_string();
+        assert_eq!(
+            merged.merge(&incompatible),
+            Err(Error::IncompatibleReports(
+                "target servers differ".to_string()
+            ))
+        );
+
+        let mut incompatible = other.clone();
+        incompatible.provenance.as_mut().unwrap().servers[0].port = 5353;
+        assert_eq!(
+            merged.merge(&incompatible),
+            Err(Error::IncompatibleReports("server sets differ".to_string()))
+        );
+
+        // shards numbered from the same start
+        let mut overlapping = other.clone();
+        overlapping.set_others_disagree(&[64].iter().cloned().collect());
+        assert_eq!(
+            report.merge(&overlapping),
+            Err(Error::IncompatibleReports(
+                "query key 64 is present in both reports".to_string()
+            ))
+        );
+    }
 }
@@ -32,6 +32,32 @@ pub enum Error {
     DatafileWrite(io::Error),
     #[error("failed to serialize datafile into JSON: {0}")]
     DatafileSerialize(#[from] serde_json::Error),
+    #[error("invalid mismatch value: {0}")]
+    InvalidMismatch(String),
+    #[error("failed to open datafile: {0}")]
+    DatafileOpen(io::Error),
+    #[error("failed to read datafile: {0}")]
+    DatafileRead(serde_json::Error),
+    #[error("failed to read datafile written by Python respdiff: {0}")]
+    DatafilePython(serde_json::Error),
+    #[error("failed to read unversioned datafile written by older respdiff-rs: {0}")]
+    DatafileLegacy(serde_json::Error),
+    #[error("unsupported datafile version {0} (this respdiff-rs supports up to {1})")]
+    DatafileVersion(u64, u32),
+    #[error("invalid datafile version {0}, expected a non-negative integer")]
+    DatafileVersionInvalid(String),
+    #[error("reports can't be merged: {0}")]
+    IncompatibleReports(String),
+    #[error("invalid size: {0}")]
+    InvalidSize(String),
+    #[error("failed to read capture file: {0}")]
+    PcapRead(io::Error),
+    #[error("invalid capture file: {0}")]
+    Pcap(String),
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("invalid zone file: {0}")]
+    InvalidZone(String),
 }
 
 impl PartialEq for Error {
@@ -50,6 +76,19 @@ impl PartialEq for Error {
             (NotImplemented, NotImplemented) => true,
             (DatafileWrite(_), DatafileWrite(_)) => true,
             (DatafileSerialize(_), DatafileSerialize(_)) => true,
+            (InvalidMismatch(a), InvalidMismatch(b)) => a == b,
+            (DatafileOpen(_), DatafileOpen(_)) => true,
+            (DatafileRead(_), DatafileRead(_)) => true,
+            (DatafilePython(_), DatafilePython(_)) => true,
+            (DatafileLegacy(_), DatafileLegacy(_)) => true,
+            (DatafileVersion(a, b), DatafileVersion(c, d)) => a == c && b == d,
+            (DatafileVersionInvalid(a), DatafileVersionInvalid(b)) => a == b,
+            (IncompatibleReports(a), IncompatibleReports(b)) => a == b,
+            (InvalidSize(a), InvalidSize(b)) => a == b,
+            (PcapRead(_), PcapRead(_)) => true,
+            (Pcap(a), Pcap(b)) => a == b,
+            (InvalidQuery(a), InvalidQuery(b)) => a == b,
+            (InvalidZone(a), InvalidZone(b)) => a == b,
             _ => false,
         }
     }
@@ -64,10 +103,27 @@ impl From<DbFormatError> for Error {
 
 #[derive(Error, Debug, PartialEq, Eq)]
 pub enum DbFormatError {
-    #[error("unsupported binary format version")]
-    Unsupported,
+    #[error("unsupported binary format version {found:?} (supported: {expected})")]
+    Unsupported { found: String, expected: String },
+    #[error("meta db is missing {0}")]
+    MissingMetadata(String),
+    #[error("meta db contains invalid {0}")]
+    InvalidMetadata(String),
+    #[error("servers in meta db {stored:?} don't match configured servers {configured:?}")]
+    ServersMismatch {
+        stored: Vec<String>,
+        configured: Vec<String>,
+    },
     #[error("reply in answers db is missing data")]
     ReplyMissingData,
     #[error("reply in answers db contains invalid data")]
     ReplyInvalidData,
+    #[error("query in queries db isn't a valid DNS message")]
+    QueryInvalidData,
+    #[error("answers for query {key} contain {found} replies, expected {expected}")]
+    ReplyCountMismatch {
+        key: u32,
+        expected: usize,
+        found: usize,
+    },
 }
@@ -0,0 +1,265 @@
+use crate::{
+    database::{self, answersdb, metadb, queriesdb},
+    error::{DbFormatError, Error},
+    QKey, ServerResponse,
+};
+use byteorder::{ByteOrder, LittleEndian};
+use lmdb::{Cursor, Environment, Transaction};
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Problem found in LMDB by `check_env()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// Record with a key which isn't a 4-byte QKey.
+    InvalidKey {
+        db: &'static str,
+        key: Vec<u8>,
+    },
+    AnswerWithoutQuery(QKey),
+    QueryWithoutAnswer(QKey),
+    /// Query which isn't a DNS message with a question.
+    MalformedQuery(QKey),
+    /// Answer list with a different number of responses than stored servers.
+    ResponseCount {
+        key: QKey,
+        expected: usize,
+        found: usize,
+    },
+    /// Answer list which ends in the middle of a response.
+    TruncatedAnswer(QKey),
+    /// Answer list with invalid response header.
+    InvalidAnswer(QKey),
+    /// Response which isn't a DNS message.
+    MalformedResponse {
+        key: QKey,
+        server: String,
+    },
+}
+
+impl Problem {
+    /// Return the key of the affected query, if it is known.
+    pub fn key(&self) -> Option<QKey> {
+        match self {
+            Problem::InvalidKey { .. } => None,
+            Problem::AnswerWithoutQuery(key)
+            | Problem::QueryWithoutAnswer(key)
+            | Problem::MalformedQuery(key)
+            | Problem::TruncatedAnswer(key)
+            | Problem::InvalidAnswer(key) => Some(*key),
+            Problem::ResponseCount { key, .. } | Problem::MalformedResponse { key, .. } => {
+                Some(*key)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::InvalidKey { db, key } => write!(f, "invalid key {:02x?} in {} db", key, db),
+            Problem::AnswerWithoutQuery(key) => write!(f, "{}: answers without query", key),
+            Problem::QueryWithoutAnswer(key) => write!(f, "{}: query without answers", key),
+            Problem::MalformedQuery(key) => write!(f, "{}: query isn't a valid DNS message", key),
+            Problem::ResponseCount {
+                key,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{}: {} responses instead of {} (number of servers)",
+                key, found, expected
+            ),
+            Problem::TruncatedAnswer(key) => write!(f, "{}: truncated answers", key),
+            Problem::InvalidAnswer(key) => write!(f, "{}: invalid answers data", key),
+            Problem::MalformedResponse { key, server } => {
+                write!(f, "{}: response from {} is malformed", key, server)
+            }
+        }
+    }
+}
+
+/// Walk ``meta``, ``queries`` and ``answers`` dbs and return all problems found.
+///
+/// Unlike other functions reading LMDB, this doesn't stop at the first invalid record. An error
+/// is returned only if the dbs can't be read at all, e.g. when ``meta`` db is missing.
+pub fn check_env(env: &Environment) -> Result<Vec<Problem>, Error> {
+    let mdb = database::open_db(env, metadb::NAME, false)?;
+    let qdb = database::open_db(env, queriesdb::NAME, false)?;
+    let adb = database::open_db(env, answersdb::NAME, false)?;
+    let txn = env.begin_ro_txn()?;
+    let format = metadb::check_version(mdb, &txn)?;
+    let servers = metadb::read_servers(mdb, &txn)?;
+
+    let mut problems = Vec::new();
+
+    let mut queries = BTreeSet::new();
+    let mut cur = txn.open_ro_cursor(qdb)?;
+    for res in cur.iter() {
+        let (key, wire) = res?;
+        if key.len() != 4 {
+            problems.push(Problem::InvalidKey {
+                db: queriesdb::NAME,
+                key: key.to_vec(),
+            });
+            continue;
+        }
+        let query = queriesdb::Query::from((key, wire));
+        if query.question().is_err() {
+            problems.push(Problem::MalformedQuery(query.key));
+        }
+        queries.insert(query.key);
+    }
+
+    let mut answers = BTreeSet::new();
+    let mut cur = txn.open_ro_cursor(adb)?;
+    for res in cur.iter() {
+        let (key, data) = res?;
+        if key.len() != 4 {
+            problems.push(Problem::InvalidKey {
+                db: answersdb::NAME,
+                key: key.to_vec(),
+            });
+            continue;
+        }
+        let qkey = LittleEndian::read_u32(key);
+        answers.insert(qkey);
+        if !queries.contains(&qkey) {
+            problems.push(Problem::AnswerWithoutQuery(qkey));
+        }
+        let list = match answersdb::parse_response_list((key, data), format) {
+            Ok(list) => list,
+            Err(DbFormatError::ReplyMissingData) => {
+                problems.push(Problem::TruncatedAnswer(qkey));
+                continue;
+            }
+            Err(_) => {
+                problems.push(Problem::InvalidAnswer(qkey));
+                continue;
+            }
+        };
+        if list.replies.len() != servers.len() {
+            problems.push(Problem::ResponseCount {
+                key: qkey,
+                expected: servers.len(),
+                found: list.replies.len(),
+            });
+        }
+        for (i, reply) in list.replies.iter().enumerate() {
+            let malformed = match reply {
+                ServerResponse::Timeout => false,
+                ServerResponse::Malformed => true,
+                ServerResponse::Data(reply) => reply.parse_sections().is_err(),
+            };
+            if malformed {
+                let server = servers.get(i).cloned().unwrap_or_else(|| format!("#{}", i));
+                problems.push(Problem::MalformedResponse { key: qkey, server });
+            }
+        }
+    }
+
+    problems.extend(
+        queries
+            .difference(&answers)
+            .map(|key| Problem::QueryWithoutAnswer(*key)),
+    );
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::BinFormat;
+    use crate::transceive::{RawResponse, RawResponseList};
+    use domain::base::{iana::rtype::Rtype, name::Dname, MessageBuilder};
+    use lmdb::WriteFlags;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    #[test]
+    fn check() {
+        let dir = TempDir::new("test").unwrap();
+        let env = database::open_env(dir.path()).unwrap();
+        let mdb = database::open_db(&env, metadb::NAME, true).unwrap();
+        let qdb = database::open_db(&env, queriesdb::NAME, true).unwrap();
+        let adb = database::open_db(&env, answersdb::NAME, true).unwrap();
+
+        let mut query = MessageBuilder::new_vec().question();
+        query
+            .push((Dname::vec_from_str("example.com.").unwrap(), Rtype::A))
+            .unwrap();
+        let query = query.finish();
+        let answers = |responses: Vec<RawResponse>| {
+            answersdb::serialize_response_list(
+                RawResponseList {
+                    key: 0,
+                    responses,
+                    meta: vec![],
+                },
+                BinFormat::DEFAULT,
+            )
+        };
+        let data = RawResponse::Data {
+            delay: Duration::from_micros(1),
+            wire: query.clone(),
+        };
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        metadb::write_version(mdb, &mut txn, BinFormat::DEFAULT).unwrap();
+        metadb::write_servers(mdb, &mut txn, vec!["a".to_string(), "b".to_string()]).unwrap();
+        let put = |txn: &mut lmdb::RwTransaction, db, key: QKey, value: &[u8]| {
+            txn.put(db, &key.to_le_bytes(), &value, WriteFlags::empty())
+                .unwrap()
+        };
+        // 1: ok, 2: no answers, 3: malformed query, 4: response count, 5: truncated,
+        // 6: malformed response, 7: no query, 8: response with malformed question
+        for key in (1..=6).chain([8]) {
+            put(&mut txn, qdb, key, if key == 3 { &[0] } else { &query });
+        }
+        let ok = answers(vec![data.clone(), RawResponse::Timeout]);
+        put(&mut txn, adb, 1, &ok);
+        put(&mut txn, adb, 3, &ok);
+        put(&mut txn, adb, 4, &answers(vec![data.clone()]));
+        put(&mut txn, adb, 5, &ok[..ok.len() - 1]);
+        let malformed = RawResponse::Data {
+            delay: Duration::from_micros(1),
+            wire: vec![0],
+        };
+        put(&mut txn, adb, 6, &answers(vec![data.clone(), malformed]));
+        put(&mut txn, adb, 7, &ok);
+        let mut bogus_section = query[..12].to_vec();
+        bogus_section.extend([0x3f, b'x', 0x00]);
+        let bogus_section = RawResponse::Data {
+            delay: Duration::from_micros(1),
+            wire: bogus_section,
+        };
+        put(&mut txn, adb, 8, &answers(vec![bogus_section, data]));
+        txn.commit().unwrap();
+
+        let problems = check_env(&env).unwrap();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::MalformedQuery(3),
+                Problem::ResponseCount {
+                    key: 4,
+                    expected: 2,
+                    found: 1
+                },
+                Problem::TruncatedAnswer(5),
+                Problem::MalformedResponse {
+                    key: 6,
+                    server: "b".to_string()
+                },
+                Problem::AnswerWithoutQuery(7),
+                Problem::MalformedResponse {
+                    key: 8,
+                    server: "a".to_string()
+                },
+                Problem::QueryWithoutAnswer(2),
+            ]
+        );
+        assert_eq!(problems[0].key(), Some(3));
+        assert_eq!(problems[6].to_string(), "2: query without answers");
+    }
+}
@@ -1,8 +1,10 @@
-use domain::base::{iana::rtype::Rtype, octets::ParseError, Message};
-use domain::rdata::Rrsig;
+use crate::config::TransportProtocol;
+use domain::base::{iana::rtype::Rtype, name::ParsedDname, octets::ParseError, Message};
+use domain::rdata::{AllRecordData, Rrsig};
 use serde::Deserialize;
 use std::collections::BTreeSet;
 use std::fmt;
+use std::io;
 use std::time::Duration;
 
 /// Configuration file.
@@ -13,10 +15,24 @@ pub mod database;
 pub mod dataformat;
 /// Respdiff errors.
 pub mod error;
+/// Integrity checks of LMDB database.
+pub mod integrity;
 /// Logic for comparing DNS messages.
 pub mod matcher;
+/// Rendering of reports in various output formats.
+pub mod output;
+/// Reading packets from pcap and pcapng files.
+pub mod pcap;
+/// Extracting DNS queries from captured traffic.
+pub mod qprep;
+/// Comparison of reports from different runs.
+pub mod sumcmp;
+/// Statistics of reports from repeated runs.
+pub mod sumstat;
 /// Sending DNS queries and receving reponses (async).
 pub mod transceive;
+/// Generating queries from zone files.
+pub mod zone;
 
 // -------- Types ---------
 
@@ -46,6 +62,45 @@ pub enum ServerResponse {
     Data(DnsReply),
 }
 
+/// Reason why no response was received from a server.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum FailureKind {
+    Timeout,
+    /// Connection refused, e.g. ICMP port unreachable for UDP.
+    Refused,
+    /// ICMP host or network unreachable.
+    Unreachable,
+    Other,
+}
+
+impl From<&io::Error> for FailureKind {
+    fn from(error: &io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut => FailureKind::Timeout,
+            io::ErrorKind::ConnectionRefused => FailureKind::Refused,
+            io::ErrorKind::HostUnreachable | io::ErrorKind::NetworkUnreachable => {
+                FailureKind::Unreachable
+            }
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+/// Details about the exchange with a server (stored since answers format v2).
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct ResponseMeta {
+    /// Transport protocol the response was received over.
+    pub transport: TransportProtocol,
+    /// Time when the query was sent, in microseconds since Unix epoch.
+    pub sent_at: u64,
+    /// Number of times the query was resent to the server.
+    pub retries: u8,
+    /// Whether the query was retried over TCP after a truncated UDP response.
+    pub tcp_fallback: bool,
+    /// Reason why no response was received, if any.
+    pub failure: Option<FailureKind>,
+}
+
 /// A set of responses from all servers for a particular query.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct ServerResponseList {
@@ -53,6 +108,16 @@ pub struct ServerResponseList {
     pub key: QKey,
     /// List of responses in the exact order as read from LMDB.
     pub replies: Vec<ServerResponse>, // TODO maybe rename -> responses
+    /// Details about each response, empty for answers format v1.
+    pub meta: Vec<ResponseMeta>,
+}
+
+/// Section of a DNS message which contains resource records.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Section {
+    Answer,
+    Authority,
+    Additional,
 }
 
 /// Criteria used to compare answers.
@@ -99,6 +164,40 @@ impl DnsReply {
         }
         Ok(covered)
     }
+    /// Return records from the given section in presentation format.
+    pub fn section_records(&self, section: Section) -> Result<Vec<String>, ParseError> {
+        let rrs = match section {
+            Section::Answer => self.message.answer()?,
+            Section::Authority => self.message.authority()?,
+            Section::Additional => self.message.additional()?,
+        };
+        let mut records = Vec::new();
+        for rr in rrs {
+            if let Some(record) = rr?.into_record::<AllRecordData<_, ParsedDname<_>>>()? {
+                records.push(record.to_string());
+            }
+        }
+        Ok(records)
+    }
+    /// Parse the question and records in all sections of the message.
+    ///
+    /// Fails if any part of the message beyond the header isn't valid.
+    pub fn parse_sections(&self) -> Result<(), ParseError> {
+        for question in self.message.question() {
+            question?;
+        }
+        let mut section = self.message.answer()?;
+        loop {
+            for rr in &mut section {
+                rr?.into_record::<AllRecordData<_, ParsedDname<_>>>()?;
+            }
+            match section.next_section()? {
+                Some(next) => section = next,
+                None => break,
+            }
+        }
+        Ok(())
+    }
 }
 impl PartialEq for DnsReply {
     fn eq(&self, other: &Self) -> bool {
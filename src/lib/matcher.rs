@@ -1,4 +1,4 @@
-use crate::{DiffCriteria, DnsReply, QKey, ServerResponse};
+use crate::{error::Error, DiffCriteria, DnsReply, QKey, ServerResponse};
 use domain::base::{
     header::Flags,
     iana,
@@ -8,6 +8,7 @@ use domain::base::{
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
+use std::str::FromStr;
 
 /// Type of mismatch
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Hash)]
@@ -23,6 +24,22 @@ pub enum Field {
     AnswerRrsigs,
 }
 
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Field::Timeout => "timeout",
+            Field::Malformed => "malformed",
+            Field::Opcode => "opcode",
+            Field::Rcode => "rcode",
+            Field::Flags => "flags",
+            Field::Question => "question",
+            Field::AnswerTypes => "answertypes",
+            Field::AnswerRrsigs => "answerrrsigs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl From<&Mismatch> for Field {
     fn from(mismatch: &Mismatch) -> Field {
         match mismatch {
@@ -156,6 +173,62 @@ fn answerrrsigs_str(types: &BTreeSet<iana::rtype::Rtype>) -> String {
         .join(" ")
 }
 
+fn answertypes_from_str(value: &str) -> Result<BTreeSet<iana::rtype::Rtype>, Error> {
+    value
+        .split_whitespace()
+        .map(|t| {
+            iana::rtype::Rtype::from_str(t).map_err(|_| Error::InvalidMismatch(value.to_string()))
+        })
+        .collect()
+}
+
+fn answerrrsigs_from_str(value: &str) -> Result<BTreeSet<iana::rtype::Rtype>, Error> {
+    value
+        .split_whitespace()
+        .map(|t| {
+            t.strip_prefix("RRSIG(")
+                .and_then(|t| t.strip_suffix(')'))
+                .and_then(|t| iana::rtype::Rtype::from_str(t).ok())
+                .ok_or_else(|| Error::InvalidMismatch(value.to_string()))
+        })
+        .collect()
+}
+
+fn opcode_from_str(value: &str) -> Result<iana::opcode::Opcode, Error> {
+    iana::opcode::Opcode::from_str(value).map_err(|_| Error::InvalidMismatch(value.to_string()))
+}
+
+fn rcode_from_str(value: &str) -> Result<iana::rcode::Rcode, Error> {
+    (0..16)
+        .map(iana::rcode::Rcode::from_int)
+        .find(|rcode| rcode.to_string() == value)
+        .ok_or_else(|| Error::InvalidMismatch(value.to_string()))
+}
+
+fn flags_from_str(value: &str) -> Result<Flags, Error> {
+    Flags::from_str(value).map_err(|_| Error::InvalidMismatch(value.to_string()))
+}
+
+fn question_from_str(value: &str) -> Result<Question<Dname<Vec<u8>>>, Error> {
+    let invalid = || Error::InvalidMismatch(value.to_string());
+    let mut parts = value.split('\t');
+    let qname = parts.next().ok_or_else(invalid)?;
+    let qtype = parts.next().ok_or_else(invalid)?;
+    let qclass = parts.next().ok_or_else(invalid)?;
+    if parts.next().is_some() {
+        return Err(invalid());
+    }
+    let qname = match qname {
+        "." => Dname::root_vec(),
+        _ => Dname::vec_from_str(qname).map_err(|_| invalid())?,
+    };
+    Ok(Question::new(
+        qname,
+        iana::rtype::Rtype::from_str(qtype).map_err(|_| invalid())?,
+        iana::class::Class::from_str(qclass).map_err(|_| invalid())?,
+    ))
+}
+
 /// Single query mismatch
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub enum Mismatch {
@@ -207,6 +280,53 @@ impl Mismatch {
             Mismatch::Question(_, got) => got.to_string(),
         }
     }
+
+    /// Reconstruct a mismatch from its field and string representation of values.
+    ///
+    /// This is the inverse of `expected()` and `got()`.
+    pub fn parse(field: Field, expected: &str, got: &str) -> Result<Self, Error> {
+        let invalid = || Error::InvalidMismatch(format!("{}: {} != {}", field, expected, got));
+        match field {
+            Field::Timeout => match (expected, got) {
+                ("timeout", "answer") => Ok(Mismatch::TimeoutExpected),
+                ("answer", "timeout") => Ok(Mismatch::TimeoutGot),
+                _ => Err(invalid()),
+            },
+            Field::Malformed => match (expected, got) {
+                ("malformed", "answer") => Ok(Mismatch::MalformedExpected),
+                ("answer", "malformed") => Ok(Mismatch::MalformedGot),
+                ("malformed", "malformed") => Ok(Mismatch::MalformedBoth),
+                _ => Err(invalid()),
+            },
+            Field::Question => match (expected, got) {
+                ("question", "questions") => Ok(Mismatch::QuestionCount),
+                _ => Ok(Mismatch::Question(
+                    question_from_str(expected)?,
+                    question_from_str(got)?,
+                )),
+            },
+            Field::Opcode => Ok(Mismatch::Opcode(
+                opcode_from_str(expected)?,
+                opcode_from_str(got)?,
+            )),
+            Field::Rcode => Ok(Mismatch::Rcode(
+                rcode_from_str(expected)?,
+                rcode_from_str(got)?,
+            )),
+            Field::Flags => Ok(Mismatch::Flags(
+                flags_from_str(expected)?,
+                flags_from_str(got)?,
+            )),
+            Field::AnswerTypes => Ok(Mismatch::AnswerTypes(
+                answertypes_from_str(expected)?,
+                answertypes_from_str(got)?,
+            )),
+            Field::AnswerRrsigs => Ok(Mismatch::AnswerRrsigs(
+                answerrrsigs_from_str(expected)?,
+                answerrrsigs_from_str(got)?,
+            )),
+        }
+    }
 }
 
 impl fmt::Display for Mismatch {
@@ -242,10 +362,26 @@ pub fn compare(
         (_, &ServerResponse::Malformed) => {
             mismatches.insert(Mismatch::MalformedGot);
         }
-        (&ServerResponse::Data(ref expected), &ServerResponse::Data(ref got)) => {
-            for crit in criteria {
-                if let Some(mismatch) = crit.mismatch(expected, got) {
-                    mismatches.insert(mismatch);
+        (ServerResponse::Data(expected), ServerResponse::Data(got)) => {
+            match (
+                expected.parse_sections().is_ok(),
+                got.parse_sections().is_ok(),
+            ) {
+                (false, false) => {
+                    mismatches.insert(Mismatch::MalformedBoth);
+                }
+                (false, true) => {
+                    mismatches.insert(Mismatch::MalformedExpected);
+                }
+                (true, false) => {
+                    mismatches.insert(Mismatch::MalformedGot);
+                }
+                (true, true) => {
+                    for crit in criteria {
+                        if let Some(mismatch) = crit.mismatch(expected, got) {
+                            mismatches.insert(mismatch);
+                        }
+                    }
                 }
             }
         }
@@ -261,7 +397,6 @@ pub type FieldMismatches = HashMap<Mismatch, BTreeSet<QKey>>;
 mod tests {
     use super::*;
     use domain::base::{iana::rtype::Rtype, Message, MessageBuilder};
-    use std::str::FromStr;
     use std::time::Duration;
 
     fn reply_noerror() -> ServerResponse {
@@ -271,7 +406,7 @@ mod tests {
     fn reply_from_msg(message: Message<Vec<u8>>) -> ServerResponse {
         ServerResponse::Data(DnsReply {
             delay: Duration::from_micros(0),
-            message: message,
+            message,
         })
     }
 
@@ -292,7 +427,7 @@ mod tests {
         let res = compare(
             &ServerResponse::Timeout,
             &reply_noerror(),
-            &vec![DiffCriteria::Opcode],
+            &[DiffCriteria::Opcode],
         );
         assert_eq!(res.len(), 1);
         assert!(res.contains(&Mismatch::TimeoutExpected));
@@ -316,6 +451,15 @@ mod tests {
         let res = compare(&ServerResponse::Malformed, &reply_noerror(), &crit);
         assert_eq!(res.len(), 1);
         assert!(res.contains(&Mismatch::MalformedExpected));
+
+        // valid header with a truncated question
+        let mut wire = MessageBuilder::new_vec().finish();
+        wire[5] = 1;
+        wire.extend([0x3f, b'x']);
+        let bogus = reply_from_msg(Message::from_octets(wire).unwrap());
+        let res = compare(&reply_noerror(), &bogus, &crit);
+        assert_eq!(res.len(), 1);
+        assert!(res.contains(&Mismatch::MalformedGot));
     }
 
     #[test]
@@ -336,7 +480,7 @@ mod tests {
         assert!(res.contains(&Mismatch::Opcode(Query, Status)));
         let res = compare(r2, r1, &crit);
         assert!(res.contains(&Mismatch::Opcode(Status, Query)));
-        let res = compare(r1, r2, &vec![]);
+        let res = compare(r1, r2, &[]);
         assert_eq!(res.len(), 0);
     }
 
@@ -358,7 +502,7 @@ mod tests {
         assert!(res.contains(&Mismatch::Rcode(NoError, ServFail)));
         let res = compare(r2, r1, &crit);
         assert!(res.contains(&Mismatch::Rcode(ServFail, NoError)));
-        let res = compare(r1, r2, &vec![]);
+        let res = compare(r1, r2, &[]);
         assert_eq!(res.len(), 0);
     }
 
@@ -374,9 +518,9 @@ mod tests {
             dns.message.header_mut().set_opcode(Status);
         };
 
-        let res = compare(r1, r2, &vec![]);
+        let res = compare(r1, r2, &[]);
         assert_eq!(res.len(), 0);
-        let res = compare(r1, r2, &vec![DiffCriteria::Opcode, DiffCriteria::Rcode]);
+        let res = compare(r1, r2, &[DiffCriteria::Opcode, DiffCriteria::Rcode]);
         assert_eq!(res.len(), 2);
         assert!(res.contains(&Mismatch::Opcode(Query, Status)));
         assert!(res.contains(&Mismatch::Rcode(NoError, ServFail)));
@@ -511,4 +655,49 @@ mod tests {
             [].iter().cloned().collect(),
         )));
     }
+
+    #[test]
+    fn mismatch_parse() {
+        use iana::opcode::Opcode;
+        use iana::rcode::Rcode;
+
+        let mismatches = vec![
+            Mismatch::TimeoutExpected,
+            Mismatch::TimeoutGot,
+            Mismatch::MalformedExpected,
+            Mismatch::MalformedGot,
+            Mismatch::MalformedBoth,
+            Mismatch::QuestionCount,
+            Mismatch::Opcode(Opcode::Query, Opcode::Status),
+            Mismatch::Rcode(Rcode::NoError, Rcode::ServFail),
+            Mismatch::Rcode(Rcode::NXDomain, Rcode::Refused),
+            Mismatch::Flags(
+                Flags::from_str("QR RD RA AD").unwrap(),
+                Flags::from_str("QR RD RA").unwrap(),
+            ),
+            Mismatch::Question(
+                Question::new_in(Dname::root_vec(), Rtype::A),
+                Question::new_in(Dname::vec_from_str("example.com.").unwrap(), Rtype::Aaaa),
+            ),
+            Mismatch::AnswerTypes(
+                [Rtype::A, Rtype::Cname].iter().cloned().collect(),
+                [].iter().cloned().collect(),
+            ),
+            Mismatch::AnswerRrsigs(
+                [Rtype::Txt].iter().cloned().collect(),
+                [Rtype::A, Rtype::Aaaa].iter().cloned().collect(),
+            ),
+        ];
+        for mismatch in mismatches {
+            let field = Field::from(&mismatch);
+            assert_eq!(
+                Mismatch::parse(field, &mismatch.expected(), &mismatch.got()),
+                Ok(mismatch)
+            );
+        }
+
+        assert!(Mismatch::parse(Field::Timeout, "answer", "answer").is_err());
+        assert!(Mismatch::parse(Field::Rcode, "NOERROR", "BOGUS").is_err());
+        assert!(Mismatch::parse(Field::AnswerRrsigs, "A", "").is_err());
+    }
 }
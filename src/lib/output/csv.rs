@@ -0,0 +1,135 @@
+use crate::{dataformat::Report, output::Questions};
+use std::io::{Result, Write};
+
+/// Quote a value if it contains the delimiter, quotes or line breaks.
+fn quote(value: &str, delimiter: char) -> String {
+    if value.contains([delimiter, '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn write_row(out: &mut impl Write, values: &[String], delimiter: char) -> Result<()> {
+    let row: Vec<_> = values.iter().map(|v| quote(v, delimiter)).collect();
+    writeln!(out, "{}", row.join(&delimiter.to_string()))
+}
+
+/// Write one row per query mismatch as delimiter-separated values.
+///
+/// If `questions` are given, each row also contains the query name, type and class. These
+/// columns are empty for queries without a known question.
+pub fn write_csv(
+    out: &mut impl Write,
+    report: &Report,
+    delimiter: char,
+    questions: Option<&Questions>,
+) -> Result<()> {
+    let mut header: Vec<String> = ["qkey", "field", "expected", "got"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    if questions.is_some() {
+        header.extend(["qname", "qtype", "qclass"].iter().map(|s| s.to_string()));
+    }
+    write_row(out, &header, delimiter)?;
+
+    let mut rows = Vec::new();
+    for (field, fdis) in report.target_disagreements() {
+        for mmqueries in fdis.mismatches() {
+            for key in &mmqueries.queries {
+                rows.push((*key, *field, mmqueries));
+            }
+        }
+    }
+    rows.sort_by_key(|(key, field, _)| (*key, *field));
+
+    for (key, field, mmqueries) in rows {
+        let mut row = vec![
+            key.to_string(),
+            field.to_string(),
+            mmqueries.exp_val.clone(),
+            mmqueries.got_val.clone(),
+        ];
+        if let Some(questions) = questions {
+            match questions.get(&key) {
+                Some(info) => {
+                    row.extend([info.qname.clone(), info.qtype.clone(), info.qclass.clone()])
+                }
+                None => row.extend([String::new(), String::new(), String::new()]),
+            }
+        }
+        write_row(out, &row, delimiter)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataformat::QueryInfo;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use domain::base::iana::{rcode::Rcode, rtype::Rtype};
+    use domain::base::{name::Dname, question::Question};
+
+    fn report() -> Report {
+        let mut rcodes = FieldMismatches::new();
+        rcodes.insert(
+            Mismatch::Rcode(Rcode::NoError, Rcode::ServFail),
+            [2, 1].iter().cloned().collect(),
+        );
+        let mut answertypes = FieldMismatches::new();
+        answertypes.insert(
+            Mismatch::AnswerTypes(
+                [Rtype::A, Rtype::Cname].iter().cloned().collect(),
+                [].iter().cloned().collect(),
+            ),
+            [1].iter().cloned().collect(),
+        );
+        let mut report = Report::new();
+        report.set_target_disagrees(
+            [(Field::Rcode, rcodes), (Field::AnswerTypes, answertypes)]
+                .into_iter()
+                .collect(),
+        );
+        report
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        write_csv(&mut out, &report(), ',', None).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "qkey,field,expected,got\n\
+            1,rcode,NOERROR,SERVFAIL\n\
+            1,answertypes,A CNAME,\n\
+            2,rcode,NOERROR,SERVFAIL\n"
+        );
+        assert_eq!(quote("a,\"b\"", ','), "\"a,\"\"b\"\"\"");
+        assert_eq!(quote("a,b", '\t'), "a,b");
+    }
+
+    #[test]
+    fn tsv_questions() {
+        let questions = [(
+            2,
+            QueryInfo::from(&Question::new_in(
+                Dname::vec_from_str("example.com").unwrap(),
+                Rtype::Aaaa,
+            )),
+        )]
+        .into_iter()
+        .collect();
+        let mut out = Vec::new();
+        write_csv(&mut out, &report(), '\t', Some(&questions)).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines[0], "qkey\tfield\texpected\tgot\tqname\tqtype\tqclass");
+        assert_eq!(lines[1], "1\trcode\tNOERROR\tSERVFAIL\t\t\t");
+        assert_eq!(
+            lines[3],
+            "2\trcode\tNOERROR\tSERVFAIL\texample.com.\tAAAA\tIN"
+        );
+    }
+}
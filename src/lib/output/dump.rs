@@ -0,0 +1,231 @@
+use crate::{
+    database::queriesdb::Query, dataformat::QueryInfo, DnsReply, QKey, Section, ServerResponse,
+};
+use domain::base::{iana::Rtype, name::ParsedDname, octets::ParseError};
+use domain::rdata::AllRecordData;
+use serde::Serialize;
+use std::io::{Result, Write};
+
+/// Stored query with the responses of all servers, a single line of the dump.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpEntry {
+    pub key: QKey,
+    /// Decoded question, missing if the query isn't a valid DNS message.
+    pub question: Option<QueryInfo>,
+    pub responses: Vec<DumpResponse>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResponseStatus {
+    Data,
+    Timeout,
+    Malformed,
+}
+
+/// Response of a single server.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpResponse {
+    pub server: String,
+    pub status: ResponseStatus,
+    /// Delay in microseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+    #[serde(flatten)]
+    pub message: Option<DumpMessage>,
+}
+
+/// Header and sections of a response in presentation format.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpMessage {
+    pub id: u16,
+    pub opcode: String,
+    pub rcode: String,
+    pub flags: String,
+    pub question: Vec<String>,
+    pub answer: Vec<String>,
+    pub authority: Vec<String>,
+    /// Additional records except for OPT, which is decoded in `edns`.
+    pub additional: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edns: Option<DumpEdns>,
+}
+
+/// EDNS parameters from the OPT record.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct DumpEdns {
+    pub version: u8,
+    pub udp_payload_size: u16,
+    pub dnssec_ok: bool,
+}
+
+impl TryFrom<&DnsReply> for DumpMessage {
+    type Error = ParseError;
+
+    fn try_from(reply: &DnsReply) -> std::result::Result<Self, Self::Error> {
+        let header = reply.message.header();
+        let mut question = Vec::new();
+        for q in reply.message.question() {
+            question.push(QueryInfo::from(&q?).to_string());
+        }
+        let mut additional = Vec::new();
+        for rr in reply.message.additional()? {
+            let rr = rr?;
+            if rr.rtype() == Rtype::Opt {
+                continue;
+            }
+            if let Some(record) = rr.into_record::<AllRecordData<_, ParsedDname<_>>>()? {
+                additional.push(record.to_string());
+            }
+        }
+        let edns = reply.message.opt().map(|opt| DumpEdns {
+            version: opt.version(),
+            udp_payload_size: opt.udp_payload_size(),
+            dnssec_ok: opt.dnssec_ok(),
+        });
+        Ok(DumpMessage {
+            id: header.id(),
+            opcode: header.opcode().to_string(),
+            rcode: header.rcode().to_string(),
+            flags: header.flags().to_string(),
+            question,
+            answer: reply.section_records(Section::Answer)?,
+            authority: reply.section_records(Section::Authority)?,
+            additional,
+            edns,
+        })
+    }
+}
+
+impl DumpEntry {
+    /// Create an entry from the query and its responses, in the same order as `servers`.
+    ///
+    /// Responses whose sections can't be parsed are reported as malformed.
+    pub fn new(query: &Query, responses: &[ServerResponse], servers: &[String]) -> Self {
+        let responses = responses
+            .iter()
+            .enumerate()
+            .map(|(i, response)| {
+                let server = servers.get(i).cloned().unwrap_or_else(|| format!("#{}", i));
+                let (status, delay, message) = match response {
+                    ServerResponse::Timeout => (ResponseStatus::Timeout, None, None),
+                    ServerResponse::Malformed => (ResponseStatus::Malformed, None, None),
+                    ServerResponse::Data(reply) => {
+                        let delay = Some(reply.delay.as_micros() as u64);
+                        match DumpMessage::try_from(reply) {
+                            Ok(message) => (ResponseStatus::Data, delay, Some(message)),
+                            Err(_) => (ResponseStatus::Malformed, delay, None),
+                        }
+                    }
+                };
+                DumpResponse {
+                    server,
+                    status,
+                    delay,
+                    message,
+                }
+            })
+            .collect();
+        DumpEntry {
+            key: query.key,
+            question: query.question().ok().map(|q| QueryInfo::from(&q)),
+            responses,
+        }
+    }
+}
+
+/// Write the entry as a single line of JSON.
+pub fn write_entry(out: &mut impl Write, entry: &DumpEntry) -> Result<()> {
+    serde_json::to_writer(&mut *out, entry)?;
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::base::{
+        iana::{Class, Rcode, Rtype},
+        name::Dname,
+        Message, MessageBuilder,
+    };
+    use domain::rdata::A;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    #[test]
+    fn entry() {
+        let qname = Dname::<Vec<u8>>::from_str("example.com.").unwrap();
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&qname, Rtype::A)).unwrap();
+        let query = Query {
+            key: 3,
+            wire: query.finish(),
+        };
+
+        let mut response = MessageBuilder::new_vec();
+        response.header_mut().set_id(42);
+        response.header_mut().set_qr(true);
+        response.header_mut().set_rcode(Rcode::NXDomain);
+        let mut response = response.question();
+        response.push((&qname, Rtype::A)).unwrap();
+        let mut response = response.answer();
+        response
+            .push((&qname, Class::In, 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let mut response = response.additional();
+        response
+            .opt(|opt| {
+                opt.set_udp_payload_size(1232);
+                opt.set_dnssec_ok(true);
+                Ok(())
+            })
+            .unwrap();
+        let response = Message::from_octets(response.finish()).unwrap();
+
+        let responses = [
+            ServerResponse::Data(DnsReply {
+                delay: Duration::from_micros(1500),
+                message: response,
+            }),
+            ServerResponse::Timeout,
+            ServerResponse::Malformed,
+            ServerResponse::Data(DnsReply {
+                delay: Duration::from_micros(1),
+                message: Message::from_octets(query.wire[..query.wire.len() - 1].to_vec()).unwrap(),
+            }),
+        ];
+        let servers = ["a".to_string(), "b".to_string()];
+        let entry = DumpEntry::new(&query, &responses, &servers);
+
+        let mut out = Vec::new();
+        write_entry(&mut out, &entry).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        assert!(line.ends_with("}\n"));
+        assert_eq!(line.lines().count(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["key"], 3);
+        assert_eq!(json["question"]["qname"], "example.com.");
+        let data = &json["responses"][0];
+        assert_eq!(data["server"], "a");
+        assert_eq!(data["status"], "data");
+        assert_eq!(data["delay"], 1500);
+        assert_eq!(data["id"], 42);
+        assert_eq!(data["rcode"], "NXDOMAIN");
+        assert_eq!(data["question"][0], "example.com. A IN");
+        assert_eq!(data["answer"][0], "example.com. 300 IN A 192.0.2.1");
+        assert_eq!(data["authority"], serde_json::json!([]));
+        assert_eq!(data["additional"], serde_json::json!([]));
+        assert_eq!(
+            data["edns"],
+            serde_json::json!({"version": 0, "udp_payload_size": 1232, "dnssec_ok": true})
+        );
+        assert_eq!(
+            json["responses"][1],
+            serde_json::json!({"server": "b", "status": "timeout"})
+        );
+        assert_eq!(json["responses"][2]["server"], "#2");
+        assert_eq!(json["responses"][2]["status"], "malformed");
+        assert_eq!(json["responses"][3]["status"], "malformed");
+    }
+}
@@ -0,0 +1,231 @@
+use crate::{
+    config::FieldWeight,
+    dataformat::{Report, Summary},
+    output::{percentage, response_lines, top_mismatches, weighted_fields, QuerySample},
+    QKey,
+};
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.2em 0.6em; text-align: left; vertical-align: top; }
+td.num { text-align: right; }
+pre { margin: 0; }
+details { margin: 0.3em 0; }
+";
+
+/// Escape text for use in HTML.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_row(out: &mut impl Write, label: &str, count: u64, pct: Option<f64>) -> Result<()> {
+    write!(
+        out,
+        "<tr><td>{}</td><td class=\"num\">{}</td>",
+        escape(label),
+        count
+    )?;
+    match pct {
+        Some(pct) => writeln!(out, "<td class=\"num\">{:.2} %</td></tr>", pct),
+        None => writeln!(out, "<td></td></tr>"),
+    }
+}
+
+fn write_sample(out: &mut impl Write, sample: &QuerySample, servers: &[String]) -> Result<()> {
+    writeln!(out, "<table>")?;
+    write!(out, "<tr>")?;
+    for name in servers.iter().take(sample.responses.len()) {
+        write!(out, "<th>{}</th>", escape(name))?;
+    }
+    writeln!(out, "</tr>")?;
+    write!(out, "<tr>")?;
+    for response in &sample.responses {
+        write!(
+            out,
+            "<td><pre>{}</pre></td>",
+            escape(&response_lines(response).join("\n"))
+        )?;
+    }
+    writeln!(out, "</tr>")?;
+    writeln!(out, "</table>")
+}
+
+/// Write a self-contained HTML report.
+///
+/// At most `limit` mismatches with the most queries are listed for each field. Queries which
+/// are present in `samples` are shown with their decoded question, and responses of the first
+/// sampled query of each mismatch are shown side by side.
+pub fn write_html(
+    out: &mut impl Write,
+    report: &Report,
+    summary: &Summary,
+    field_weights: &[FieldWeight],
+    limit: usize,
+    servers: &[String],
+    samples: &BTreeMap<QKey, QuerySample>,
+) -> Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>respdiff report</title>")?;
+    writeln!(out, "<style>{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(out, "<h1>respdiff report</h1>")?;
+
+    writeln!(out, "<h2>Global statistics</h2>\n<table>")?;
+    write_row(
+        out,
+        "duration (s)",
+        report.end_time.saturating_sub(report.start_time) as u64,
+        None,
+    )?;
+    write_row(out, "queries", report.total_queries, None)?;
+    write_row(
+        out,
+        "answers",
+        report.total_answers,
+        Some(percentage(report.total_answers, report.total_queries)),
+    )?;
+    writeln!(out, "</table>")?;
+
+    let disagrees = summary.total();
+    writeln!(out, "<h2>Differences statistics</h2>\n<table>")?;
+    write_row(
+        out,
+        "upstream unstable",
+        summary.upstream_unstable,
+        Some(percentage(summary.upstream_unstable, report.total_answers)),
+    )?;
+    write_row(
+        out,
+        "not 100% reproducible",
+        summary.not_reproducible,
+        Some(percentage(summary.not_reproducible, report.total_answers)),
+    )?;
+    write_row(
+        out,
+        "target disagrees",
+        disagrees,
+        Some(percentage(disagrees, summary.usable_answers)),
+    )?;
+    writeln!(out, "</table>")?;
+
+    let fields = weighted_fields(summary, field_weights);
+    writeln!(out, "<h2>Target disagreements</h2>\n<table>")?;
+    writeln!(out, "<tr><th>field</th><th>count</th><th>%</th></tr>")?;
+    for (field, fdis) in &fields {
+        write_row(
+            out,
+            &field.to_string(),
+            fdis.count(),
+            Some(percentage(fdis.count(), disagrees)),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    for (field, fdis) in &fields {
+        writeln!(out, "<h3>{} mismatches</h3>", escape(&field.to_string()))?;
+        for mmqueries in top_mismatches(fdis, limit) {
+            let count = mmqueries.queries.len() as u64;
+            writeln!(
+                out,
+                "<h4>{} != {} &mdash; {} ({:.2} %)</h4>",
+                escape(&mmqueries.exp_val),
+                escape(&mmqueries.got_val),
+                count,
+                percentage(count, fdis.count())
+            )?;
+
+            writeln!(out, "<details><summary>{} queries</summary><ul>", count)?;
+            for key in &mmqueries.queries {
+                match samples.get(key).and_then(|s| s.question.as_ref()) {
+                    Some(question) => {
+                        writeln!(out, "<li>{}: <code>{}</code></li>", key, escape(question))?
+                    }
+                    None => writeln!(out, "<li>{}</li>", key)?,
+                }
+            }
+            writeln!(out, "</ul></details>")?;
+
+            let sample = mmqueries
+                .queries
+                .iter()
+                .find_map(|key| samples.get(key))
+                .filter(|sample| !sample.responses.is_empty());
+            if let Some(sample) = sample {
+                writeln!(
+                    out,
+                    "<p>Example query {}: <code>{}</code></p>",
+                    sample.key,
+                    escape(sample.question.as_deref().unwrap_or("(unparsable)"))
+                )?;
+                write_sample(out, sample, servers)?;
+            }
+        }
+    }
+
+    writeln!(out, "</body>\n</html>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use crate::ServerResponse;
+
+    #[test]
+    fn html_escape() {
+        assert_eq!(
+            escape("<a href=\"x\">&'"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+    }
+
+    #[test]
+    fn html_report() {
+        let mut fmismatches = FieldMismatches::new();
+        fmismatches.insert(Mismatch::TimeoutGot, [1, 2].iter().cloned().collect());
+        let mut report = Report::new();
+        report.total_queries = 10;
+        report.total_answers = 10;
+        report.set_target_disagrees([(Field::Timeout, fmismatches)].into_iter().collect());
+        let weights = [FieldWeight::Timeout];
+        let summary = Summary::from_report(&report, &weights, None);
+        let samples = [(
+            2,
+            QuerySample {
+                key: 2,
+                question: Some(String::from("example.com. A IN")),
+                responses: vec![ServerResponse::Malformed, ServerResponse::Timeout],
+            },
+        )]
+        .into_iter()
+        .collect();
+        let servers = vec![String::from("ref"), String::from("target")];
+
+        let mut out = Vec::new();
+        write_html(
+            &mut out, &report, &summary, &weights, 10, &servers, &samples,
+        )
+        .unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("<h3>timeout mismatches</h3>"));
+        assert!(html.contains("<li>1</li>"));
+        assert!(html.contains("<li>2: <code>example.com. A IN</code></li>"));
+        assert!(html.contains("<th>ref</th><th>target</th>"));
+        assert!(html.contains("<td><pre>malformed</pre></td><td><pre>timeout</pre></td>"));
+        assert!(html.ends_with("</html>\n"));
+    }
+}
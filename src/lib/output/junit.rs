@@ -0,0 +1,155 @@
+use crate::{
+    config::FieldWeight,
+    dataformat::Summary,
+    output::{weighted_fields, QuerySample},
+    QKey,
+};
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+/// Number of sample queries listed in each failure.
+const EXAMPLE_QUERIES: usize = 10;
+
+/// Escape text for use in XML attributes and content.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Write summary as JUnit XML.
+///
+/// Each field is a test suite and each distinct mismatch is a failing test case. Queries
+/// which are present in `samples` are listed with their decoded question.
+pub fn write_junit(
+    out: &mut impl Write,
+    summary: &Summary,
+    field_weights: &[FieldWeight],
+    samples: &BTreeMap<QKey, QuerySample>,
+) -> Result<()> {
+    let fields = weighted_fields(summary, field_weights);
+    let tests: usize = fields.iter().map(|(_, fdis)| fdis.mismatches().len()).sum();
+
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    if tests == 0 {
+        writeln!(
+            out,
+            "<testsuites name=\"respdiff\" tests=\"1\" failures=\"0\">"
+        )?;
+        writeln!(
+            out,
+            "  <testsuite name=\"respdiff\" tests=\"1\" failures=\"0\">"
+        )?;
+        writeln!(
+            out,
+            "    <testcase classname=\"respdiff\" name=\"no target disagreements\"/>"
+        )?;
+        writeln!(out, "  </testsuite>")?;
+        return writeln!(out, "</testsuites>");
+    }
+
+    writeln!(
+        out,
+        "<testsuites name=\"respdiff\" tests=\"{}\" failures=\"{}\">",
+        tests, tests
+    )?;
+    for (field, fdis) in &fields {
+        let count = fdis.mismatches().len();
+        writeln!(
+            out,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            field, count, count
+        )?;
+        for mmqueries in fdis.mismatches() {
+            writeln!(
+                out,
+                "    <testcase classname=\"respdiff.{}\" name=\"{}\">",
+                field,
+                escape(&format!("{} != {}", mmqueries.exp_val, mmqueries.got_val))
+            )?;
+            let examples: Vec<String> = mmqueries
+                .queries
+                .iter()
+                .take(EXAMPLE_QUERIES)
+                .map(
+                    |key| match samples.get(key).and_then(|s| s.question.as_ref()) {
+                        Some(question) => format!("{}: {}", key, question),
+                        None => key.to_string(),
+                    },
+                )
+                .collect();
+            writeln!(
+                out,
+                "      <failure type=\"mismatch\" message=\"{}\">{}</failure>",
+                escape(&format!(
+                    "expected: {}, got: {} ({} queries)",
+                    mmqueries.exp_val,
+                    mmqueries.got_val,
+                    mmqueries.queries.len()
+                )),
+                escape(&format!("sample QKeys:\n{}", examples.join("\n")))
+            )?;
+            writeln!(out, "    </testcase>")?;
+        }
+        writeln!(out, "  </testsuite>")?;
+    }
+    writeln!(out, "</testsuites>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataformat::Report;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use domain::base::iana::rcode::Rcode;
+
+    #[test]
+    fn junit() {
+        let mut fmismatches = FieldMismatches::new();
+        fmismatches.insert(
+            Mismatch::Rcode(Rcode::NoError, Rcode::ServFail),
+            [1, 2].iter().cloned().collect(),
+        );
+        let mut report = Report::new();
+        report.set_target_disagrees([(Field::Rcode, fmismatches)].into_iter().collect());
+        let weights = [FieldWeight::Rcode];
+        let summary = Summary::from_report(&report, &weights, None);
+        let samples = [(
+            2,
+            QuerySample {
+                key: 2,
+                question: Some(String::from("<x>. A IN")),
+                responses: vec![],
+            },
+        )]
+        .into_iter()
+        .collect();
+
+        let mut out = Vec::new();
+        write_junit(&mut out, &summary, &weights, &samples).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("<testsuites name=\"respdiff\" tests=\"1\" failures=\"1\">"));
+        assert!(xml.contains("<testsuite name=\"rcode\" tests=\"1\" failures=\"1\">"));
+        assert!(
+            xml.contains("<testcase classname=\"respdiff.rcode\" name=\"NOERROR != SERVFAIL\">")
+        );
+        assert!(xml.contains(
+            "message=\"expected: NOERROR, got: SERVFAIL (2 queries)\">sample QKeys:&#10;1&#10;2: &lt;x&gt;. A IN</failure>"
+        ));
+
+        let mut out = Vec::new();
+        write_junit(&mut out, &Summary::default(), &weights, &samples).unwrap();
+        let xml = String::from_utf8(out).unwrap();
+        assert!(xml.contains("<testcase classname=\"respdiff\" name=\"no target disagreements\"/>"));
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    config::FieldWeight,
+    dataformat::{Report, Summary},
+    output::{percentage, top_mismatches, weighted_fields, QuerySample},
+    QKey,
+};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io::{Result, Write};
+
+/// Number of example queries listed for each mismatch.
+const EXAMPLE_QUERIES: usize = 3;
+
+/// Escape text for use in a Markdown table cell.
+fn escape(text: &str) -> String {
+    let text = text.replace('|', "\\|");
+    if text.is_empty() {
+        String::from("(empty)")
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+/// Write a compact Markdown summary suitable for merge request comments.
+///
+/// At most `limit` mismatches with the most queries are listed in total. Queries which are
+/// present in `samples` are listed with their decoded question.
+pub fn write_markdown(
+    out: &mut impl Write,
+    report: &Report,
+    summary: &Summary,
+    field_weights: &[FieldWeight],
+    limit: usize,
+    samples: &BTreeMap<QKey, QuerySample>,
+) -> Result<()> {
+    let disagrees = summary.total();
+    writeln!(
+        out,
+        "**respdiff**: {} queries, {} answers; target disagrees in {} of {} usable answers \
+        ({:.2} %), {} upstream unstable, {} not reproducible\n",
+        report.total_queries,
+        report.total_answers,
+        disagrees,
+        summary.usable_answers,
+        percentage(disagrees, summary.usable_answers),
+        summary.upstream_unstable,
+        summary.not_reproducible,
+    )?;
+
+    let fields = weighted_fields(summary, field_weights);
+    if fields.is_empty() {
+        return Ok(());
+    }
+    writeln!(out, "| field | count | % |")?;
+    writeln!(out, "|---|--:|--:|")?;
+    for (field, fdis) in &fields {
+        writeln!(
+            out,
+            "| {} | {} | {:.2} |",
+            field,
+            fdis.count(),
+            percentage(fdis.count(), disagrees)
+        )?;
+    }
+
+    let mut mismatches: Vec<_> = fields
+        .iter()
+        .flat_map(|(field, fdis)| {
+            top_mismatches(fdis, limit)
+                .into_iter()
+                .map(move |mmqueries| (*field, mmqueries))
+        })
+        .collect();
+    mismatches.sort_by_key(|(_, mmqueries)| Reverse(mmqueries.queries.len()));
+    mismatches.truncate(limit);
+
+    writeln!(out, "\n| field | expected | got | count |")?;
+    writeln!(out, "|---|---|---|--:|")?;
+    for (field, mmqueries) in &mismatches {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            field,
+            escape(&mmqueries.exp_val),
+            escape(&mmqueries.got_val),
+            mmqueries.queries.len()
+        )?;
+    }
+
+    writeln!(out, "\n<details><summary>Example queries</summary>\n")?;
+    for (field, mmqueries) in &mismatches {
+        writeln!(
+            out,
+            "- {} {} != {}",
+            field,
+            escape(&mmqueries.exp_val),
+            escape(&mmqueries.got_val)
+        )?;
+        for key in mmqueries.queries.iter().take(EXAMPLE_QUERIES) {
+            match samples.get(key).and_then(|s| s.question.as_ref()) {
+                Some(question) => writeln!(out, "  - {}: `{}`", key, question)?,
+                None => writeln!(out, "  - {}", key)?,
+            }
+        }
+    }
+    writeln!(out, "\n</details>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use domain::base::iana::rcode::Rcode;
+
+    #[test]
+    fn markdown_summary() {
+        let mut rcodes = FieldMismatches::new();
+        rcodes.insert(
+            Mismatch::Rcode(Rcode::NoError, Rcode::ServFail),
+            [1, 2, 3, 4].iter().cloned().collect(),
+        );
+        rcodes.insert(
+            Mismatch::Rcode(Rcode::NXDomain, Rcode::NoError),
+            [5].iter().cloned().collect(),
+        );
+        let mut timeouts = FieldMismatches::new();
+        timeouts.insert(Mismatch::TimeoutGot, [6, 7].iter().cloned().collect());
+        let mut report = Report::new();
+        report.total_answers = 100;
+        report.set_target_disagrees(
+            [(Field::Rcode, rcodes), (Field::Timeout, timeouts)]
+                .into_iter()
+                .collect(),
+        );
+        let weights = [FieldWeight::Timeout, FieldWeight::Rcode];
+        let summary = Summary::from_report(&report, &weights, None);
+        let samples = [(
+            1,
+            QuerySample {
+                key: 1,
+                question: Some(String::from("example.com. A IN")),
+                responses: vec![],
+            },
+        )]
+        .into_iter()
+        .collect();
+
+        let mut out = Vec::new();
+        write_markdown(&mut out, &report, &summary, &weights, 2, &samples).unwrap();
+        let md = String::from_utf8(out).unwrap();
+        assert!(md.contains("target disagrees in 7 of 100 usable answers (7.00 %)"));
+        assert!(md.contains("| timeout | 2 | 28.57 |\n| rcode | 5 | 71.43 |"));
+        assert!(md.contains("| rcode | `NOERROR` | `SERVFAIL` | 4 |"));
+        assert!(md.contains("| timeout | `answer` | `timeout` | 2 |"));
+        assert!(!md.contains("NXDOMAIN"));
+        assert!(md.contains("  - 1: `example.com. A IN`\n  - 2\n  - 3\n"));
+    }
+}
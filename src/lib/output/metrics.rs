@@ -0,0 +1,244 @@
+use crate::{dataformat::Report, ServerResponse};
+use std::io::{Result, Write};
+use std::time::Duration;
+
+/// Quantiles of response latency which are exported for each server.
+const QUANTILES: [f64; 3] = [0.5, 0.9, 0.99];
+
+/// Response statistics of a single server.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ServerStats {
+    pub name: String,
+    pub timeouts: u64,
+    pub malformed: u64,
+    delays: Vec<Duration>,
+}
+
+impl ServerStats {
+    /// Create empty statistics for a server.
+    pub fn new(name: &str) -> Self {
+        ServerStats {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Account a single response from the server.
+    pub fn add(&mut self, response: &ServerResponse) {
+        match response {
+            ServerResponse::Timeout => self.timeouts += 1,
+            ServerResponse::Malformed => self.malformed += 1,
+            ServerResponse::Data(reply) => self.delays.push(reply.delay),
+        }
+    }
+
+    /// Return the latency quantile (0.0 - 1.0) of responses with data.
+    pub fn quantile(&mut self, q: f64) -> Option<Duration> {
+        if self.delays.is_empty() {
+            return None;
+        }
+        self.delays.sort_unstable();
+        let rank = (q * (self.delays.len() - 1) as f64).round() as usize;
+        Some(self.delays[rank.min(self.delays.len() - 1)])
+    }
+}
+
+/// Escape a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn write_header(out: &mut impl Write, name: &str, mtype: &str, help: &str) -> Result<()> {
+    writeln!(out, "# TYPE respdiff_{} {}", name, mtype)?;
+    writeln!(out, "# HELP respdiff_{} {}", name, help)
+}
+
+/// Write total number of queries and answers.
+pub fn write_totals(out: &mut impl Write, total_queries: u64, total_answers: u64) -> Result<()> {
+    write_header(out, "total_queries", "gauge", "Number of queries.")?;
+    writeln!(out, "respdiff_total_queries {}", total_queries)?;
+    write_header(out, "total_answers", "gauge", "Number of answers.")?;
+    writeln!(out, "respdiff_total_answers {}", total_answers)
+}
+
+/// Write upstream unstable count and per-field and per-mismatch disagreement counts.
+pub fn write_disagreements(out: &mut impl Write, report: &Report) -> Result<()> {
+    write_header(
+        out,
+        "upstream_unstable",
+        "gauge",
+        "Number of queries on which other servers disagree.",
+    )?;
+    writeln!(
+        out,
+        "respdiff_upstream_unstable {}",
+        report.others_disagree().len()
+    )?;
+
+    write_header(
+        out,
+        "field_disagreements",
+        "gauge",
+        "Number of queries on which target disagrees in a field.",
+    )?;
+    for (field, fdis) in report.target_disagreements() {
+        writeln!(
+            out,
+            "respdiff_field_disagreements{{field=\"{}\"}} {}",
+            field,
+            fdis.count()
+        )?;
+    }
+    write_header(
+        out,
+        "mismatch_disagreements",
+        "gauge",
+        "Number of queries on which target disagrees with a particular mismatch.",
+    )?;
+    for (field, fdis) in report.target_disagreements() {
+        for mmqueries in fdis.mismatches() {
+            writeln!(
+                out,
+                "respdiff_mismatch_disagreements{{field=\"{}\",expected=\"{}\",got=\"{}\"}} {}",
+                field,
+                escape(&mmqueries.exp_val),
+                escape(&mmqueries.got_val),
+                mmqueries.queries.len()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write timeouts, malformed responses and latency quantiles of each server.
+pub fn write_servers(out: &mut impl Write, servers: &mut [ServerStats]) -> Result<()> {
+    write_header(out, "server_timeouts", "gauge", "Number of timeouts.")?;
+    for stats in servers.iter() {
+        writeln!(
+            out,
+            "respdiff_server_timeouts{{server=\"{}\"}} {}",
+            escape(&stats.name),
+            stats.timeouts
+        )?;
+    }
+    write_header(
+        out,
+        "server_malformed",
+        "gauge",
+        "Number of malformed responses.",
+    )?;
+    for stats in servers.iter() {
+        writeln!(
+            out,
+            "respdiff_server_malformed{{server=\"{}\"}} {}",
+            escape(&stats.name),
+            stats.malformed
+        )?;
+    }
+    write_header(
+        out,
+        "server_latency_seconds",
+        "summary",
+        "Latency of responses.",
+    )?;
+    for stats in servers.iter_mut() {
+        let name = escape(&stats.name);
+        for q in QUANTILES {
+            if let Some(delay) = stats.quantile(q) {
+                writeln!(
+                    out,
+                    "respdiff_server_latency_seconds{{server=\"{}\",quantile=\"{}\"}} {}",
+                    name,
+                    q,
+                    delay.as_secs_f64()
+                )?;
+            }
+        }
+        let sum: Duration = stats.delays.iter().sum();
+        writeln!(
+            out,
+            "respdiff_server_latency_seconds_sum{{server=\"{}\"}} {}",
+            name,
+            sum.as_secs_f64()
+        )?;
+        writeln!(
+            out,
+            "respdiff_server_latency_seconds_count{{server=\"{}\"}} {}",
+            name,
+            stats.delays.len()
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the terminating line of OpenMetrics exposition.
+pub fn write_eof(out: &mut impl Write) -> Result<()> {
+    writeln!(out, "# EOF")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use crate::DnsReply;
+    use domain::base::MessageBuilder;
+
+    fn data(micros: u64) -> ServerResponse {
+        ServerResponse::Data(DnsReply {
+            delay: Duration::from_micros(micros),
+            message: MessageBuilder::new_vec().into_message(),
+        })
+    }
+
+    #[test]
+    fn server_stats() {
+        let mut stats = ServerStats::new("kresd");
+        assert_eq!(stats.quantile(0.5), None);
+        for micros in [500, 100, 300, 200, 400] {
+            stats.add(&data(micros));
+        }
+        stats.add(&ServerResponse::Timeout);
+        stats.add(&ServerResponse::Malformed);
+        assert_eq!(stats.timeouts, 1);
+        assert_eq!(stats.malformed, 1);
+        assert_eq!(stats.quantile(0.0), Some(Duration::from_micros(100)));
+        assert_eq!(stats.quantile(0.5), Some(Duration::from_micros(300)));
+        assert_eq!(stats.quantile(1.0), Some(Duration::from_micros(500)));
+    }
+
+    #[test]
+    fn openmetrics() {
+        let mut fmismatches = FieldMismatches::new();
+        fmismatches.insert(Mismatch::TimeoutGot, [1, 2].iter().cloned().collect());
+        let mut report = Report::new();
+        report.total_queries = 10;
+        report.total_answers = 9;
+        report.set_target_disagrees([(Field::Timeout, fmismatches)].into_iter().collect());
+        let mut stats = ServerStats::new("kresd");
+        stats.add(&data(1000));
+        stats.add(&ServerResponse::Timeout);
+
+        let mut out = Vec::new();
+        write_totals(&mut out, report.total_queries, report.total_answers).unwrap();
+        write_disagreements(&mut out, &report).unwrap();
+        write_servers(&mut out, &mut [stats]).unwrap();
+        write_eof(&mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("respdiff_total_queries 10\n"));
+        assert!(text.contains("respdiff_total_answers 9\n"));
+        assert!(text.contains("respdiff_upstream_unstable 0\n"));
+        assert!(text.contains("respdiff_field_disagreements{field=\"timeout\"} 2\n"));
+        assert!(text.contains(
+            "respdiff_mismatch_disagreements{field=\"timeout\",expected=\"answer\",got=\"timeout\"} 2\n"
+        ));
+        assert!(text.contains("respdiff_server_timeouts{server=\"kresd\"} 1\n"));
+        assert!(text.contains(
+            "respdiff_server_latency_seconds{server=\"kresd\",quantile=\"0.5\"} 0.001\n"
+        ));
+        assert!(text.contains("respdiff_server_latency_seconds_count{server=\"kresd\"} 1\n"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,221 @@
+use crate::{
+    config::FieldWeight,
+    database::{answersdb, queriesdb, BinFormat},
+    dataformat::{FieldDisagreements, MismatchQueries, QueryInfo, Report, Summary},
+    error::Error,
+    matcher::Field,
+    QKey, Section, ServerResponse,
+};
+use lmdb::{Database, Error as LmdbError, RoTransaction};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+/// Delimiter-separated values with one row per query mismatch.
+pub mod csv;
+/// JSON Lines dump of stored queries and responses.
+pub mod dump;
+/// Self-contained HTML report.
+pub mod html;
+/// JUnit XML with mismatches as failing test cases.
+pub mod junit;
+/// Compact Markdown summary for merge request comments.
+pub mod markdown;
+/// OpenMetrics text export of run statistics.
+pub mod metrics;
+/// Export of queries and responses as captured packets.
+pub mod pcap;
+/// Human-readable text table (diffsum).
+pub mod text;
+
+/// Decoded questions of queries.
+pub type Questions = BTreeMap<QKey, QueryInfo>;
+
+/// Decoded query with server responses, used as an example in rendered reports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuerySample {
+    pub key: QKey,
+    /// Question in presentation format, if the query could be decoded.
+    pub question: Option<String>,
+    /// Responses in the same order as servers in LMDB.
+    pub responses: Vec<ServerResponse>,
+}
+
+impl QuerySample {
+    /// Load the query and its responses stored in the given format from LMDB.
+    pub fn load(
+        qdb: Database,
+        adb: Database,
+        txn: &RoTransaction,
+        key: QKey,
+        format: BinFormat,
+    ) -> Result<Self, Error> {
+        let question = queriesdb::get_query(qdb, txn, key)?
+            .question()
+            .ok()
+            .map(|q| QueryInfo::from(&q).to_string());
+        let responses = match answersdb::get_response_list(adb, txn, key, format) {
+            Ok(list) => list.replies,
+            Err(Error::Database(LmdbError::NotFound)) => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(QuerySample {
+            key,
+            question,
+            responses,
+        })
+    }
+}
+
+/// Create samples without responses from questions embedded in the report.
+pub fn embedded_samples(report: &Report) -> BTreeMap<QKey, QuerySample> {
+    report
+        .questions
+        .iter()
+        .map(|(key, info)| {
+            let sample = QuerySample {
+                key: *key,
+                question: Some(info.to_string()),
+                responses: Vec::new(),
+            };
+            (*key, sample)
+        })
+        .collect()
+}
+
+/// Load questions of the given queries from LMDB.
+///
+/// Queries which can't be parsed are left out.
+pub fn load_questions(
+    qdb: Database,
+    txn: &RoTransaction,
+    keys: impl IntoIterator<Item = QKey>,
+) -> Result<Questions, Error> {
+    let mut questions = BTreeMap::new();
+    for key in keys {
+        if let Ok(question) = queriesdb::get_query(qdb, txn, key)?.question() {
+            questions.insert(key, QueryInfo::from(&question));
+        }
+    }
+    Ok(questions)
+}
+
+/// Return a short text representation of a response: header line and answer records.
+pub fn response_lines(response: &ServerResponse) -> Vec<String> {
+    match response {
+        ServerResponse::Timeout => vec![String::from("timeout")],
+        ServerResponse::Malformed => vec![String::from("malformed")],
+        ServerResponse::Data(reply) => {
+            let header = reply.message.header();
+            let mut lines = vec![format!("{} {}", header.rcode(), header.flags())];
+            match reply.section_records(Section::Answer) {
+                Ok(records) => lines.extend(records),
+                Err(_) => lines.push(String::from("(unparsable answer)")),
+            }
+            lines
+        }
+    }
+}
+
+/// Return summary fields ordered by their weight.
+///
+/// Fields which aren't listed in `field_weights` are put last.
+pub fn weighted_fields<'a>(
+    summary: &'a Summary,
+    field_weights: &[FieldWeight],
+) -> Vec<(Field, &'a FieldDisagreements)> {
+    let mut fields: Vec<_> = summary
+        .fields()
+        .iter()
+        .map(|(field, fdis)| (*field, fdis))
+        .collect();
+    fields.sort_by_key(|(field, _)| {
+        field_weights
+            .iter()
+            .position(|fw| *fw == FieldWeight::from(*field))
+            .unwrap_or(usize::MAX)
+    });
+    fields
+}
+
+/// Return at most `limit` mismatches with the most queries.
+pub fn top_mismatches(fdis: &FieldDisagreements, limit: usize) -> Vec<&MismatchQueries> {
+    let mut mismatches: Vec<_> = fdis.mismatches().iter().collect();
+    mismatches.sort_by_key(|mmqueries| Reverse(mmqueries.queries.len()));
+    mismatches.truncate(limit);
+    mismatches
+}
+
+/// Return the percentage of `part` in `total`.
+pub fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    100.0 * part as f64 / total as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database;
+    use crate::transceive::{RawResponse, RawResponseList};
+    use domain::base::{iana::rtype::Rtype, name::Dname, MessageBuilder};
+    use domain::rdata::A;
+    use lmdb::{Transaction, WriteFlags};
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    #[test]
+    fn load_sample() {
+        let dir = TempDir::new("test").unwrap();
+        let env = database::open_env(dir.path()).unwrap();
+        let qdb = database::open_db(&env, queriesdb::NAME, true).unwrap();
+        let adb = database::open_db(&env, answersdb::NAME, true).unwrap();
+
+        let qname = Dname::vec_from_str("example.com.").unwrap();
+        let mut query = MessageBuilder::new_vec().question();
+        query.push((&qname, Rtype::A)).unwrap();
+        let mut answer = query.clone().answer();
+        answer.header_mut().set_qr(true);
+        answer
+            .push((&qname, 300, A::from_octets(192, 0, 2, 1)))
+            .unwrap();
+        let responses = answersdb::serialize_response_list(
+            RawResponseList {
+                key: 1,
+                responses: vec![
+                    RawResponse::Data {
+                        delay: Duration::from_micros(10),
+                        wire: answer.finish(),
+                    },
+                    RawResponse::Timeout,
+                ],
+                meta: vec![],
+            },
+            BinFormat::V2,
+        );
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        let key = [1, 0, 0, 0];
+        txn.put(qdb, &key, &query.finish(), WriteFlags::empty())
+            .unwrap();
+        txn.put(adb, &key, &responses, WriteFlags::empty()).unwrap();
+        txn.put(qdb, &[2, 0, 0, 0], &[0], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        let sample = QuerySample::load(qdb, adb, &txn, 1, BinFormat::V2).unwrap();
+        assert_eq!(sample.question.as_deref(), Some("example.com. A IN"));
+        assert_eq!(sample.responses.len(), 2);
+        assert_eq!(
+            response_lines(&sample.responses[0]),
+            vec!["NOERROR QR", "example.com. 300 IN A 192.0.2.1"]
+        );
+        assert_eq!(response_lines(&sample.responses[1]), vec!["timeout"]);
+
+        let sample = QuerySample::load(qdb, adb, &txn, 2, BinFormat::V2).unwrap();
+        assert_eq!(sample.question, None);
+        assert!(sample.responses.is_empty());
+        assert!(QuerySample::load(qdb, adb, &txn, 3, BinFormat::V2).is_err());
+    }
+}
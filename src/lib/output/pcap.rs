@@ -0,0 +1,235 @@
+use crate::{pcap::PcapWriter, transceive::RawResponse, QKey, ResponseMeta};
+use std::io::{Result, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Synthetic client addresses from documentation prefixes.
+const CLIENT_IPV4: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 1);
+const CLIENT_IPV6: Ipv6Addr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+
+const IPPROTO_UDP: u8 = 17;
+
+/// Return client port of the query, unique among 64512 consecutive keys.
+fn client_port(key: QKey) -> u16 {
+    1024 + (key % (u16::MAX as u32 - 1023)) as u16
+}
+
+/// Internet checksum of the data.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Build an IPv4 or IPv6 packet with a UDP datagram.
+///
+/// Returns `None` if the addresses are from different families or the payload doesn't fit.
+pub fn udp_packet(src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Option<Vec<u8>> {
+    let udp_len = u16::try_from(8 + payload.len()).ok()?;
+    let mut udp = Vec::with_capacity(udp_len as usize);
+    udp.extend(src.port().to_be_bytes());
+    udp.extend(dst.port().to_be_bytes());
+    udp.extend(udp_len.to_be_bytes());
+    udp.extend([0, 0]);
+    udp.extend(payload);
+
+    let (mut packet, mut pseudo) = match (src.ip(), dst.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            let total_len = u16::try_from(20 + udp.len()).ok()?;
+            let mut ip = vec![0x45, 0];
+            ip.extend(total_len.to_be_bytes());
+            ip.extend([0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0]);
+            ip.extend(src.octets());
+            ip.extend(dst.octets());
+            let sum = checksum(&ip);
+            ip[10..12].copy_from_slice(&sum.to_be_bytes());
+
+            let mut pseudo = [src.octets(), dst.octets()].concat();
+            pseudo.extend([0, IPPROTO_UDP]);
+            pseudo.extend(udp_len.to_be_bytes());
+            (ip, pseudo)
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            let mut ip = vec![0x60, 0, 0, 0];
+            ip.extend(udp_len.to_be_bytes());
+            ip.extend([IPPROTO_UDP, 64]);
+            ip.extend(src.octets());
+            ip.extend(dst.octets());
+
+            let mut pseudo = [src.octets(), dst.octets()].concat();
+            pseudo.extend((udp_len as u32).to_be_bytes());
+            pseudo.extend([0, 0, 0, IPPROTO_UDP]);
+            (ip, pseudo)
+        }
+        _ => return None,
+    };
+
+    pseudo.extend(&udp);
+    let sum = match checksum(&pseudo) {
+        0 => 0xffff,
+        sum => sum,
+    };
+    udp[6..8].copy_from_slice(&sum.to_be_bytes());
+    packet.extend(udp);
+    Some(packet)
+}
+
+/// Write the query sent to each server and the responses of servers which answered, in the
+/// order of their timestamps.
+///
+/// `responses` and `meta` are in the same order as `servers`, or empty if not stored. Packets
+/// use raw IP link type and a synthetic client address with port derived from the key. Queries
+/// are timestamped at their send time from `meta` if known, or at `start` otherwise, and
+/// responses at the send time plus their delay. Responses are written as received, including
+/// malformed ones, and timeouts have no response packet.
+pub fn write_exchange<W: Write>(
+    out: &mut PcapWriter<W>,
+    key: QKey,
+    query: &[u8],
+    servers: &[SocketAddr],
+    responses: &[RawResponse],
+    meta: &[ResponseMeta],
+    start: Duration,
+) -> Result<()> {
+    let client = |server: &SocketAddr| {
+        let ip = match server {
+            SocketAddr::V4(_) => IpAddr::V4(CLIENT_IPV4),
+            SocketAddr::V6(_) => IpAddr::V6(CLIENT_IPV6),
+        };
+        SocketAddr::new(ip, client_port(key))
+    };
+
+    let mut packets = Vec::new();
+    for (i, server) in servers.iter().enumerate() {
+        let sent = match meta.get(i) {
+            Some(meta) if meta.sent_at > 0 => Duration::from_micros(meta.sent_at),
+            _ => start,
+        };
+        packets.extend(udp_packet(client(server), *server, query).map(|p| (sent, p)));
+        if let Some(RawResponse::Data { delay, wire }) = responses.get(i) {
+            packets.extend(udp_packet(*server, client(server), wire).map(|p| (sent + *delay, p)));
+        }
+    }
+    packets.sort_by_key(|(time, _)| *time);
+    for (time, packet) in packets {
+        out.write_packet(time, &packet)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pcap::{PcapReader, LINKTYPE_RAW};
+    use crate::qprep::QueryCollector;
+    use domain::base::{iana::Rtype, name::Dname, MessageBuilder};
+    use std::str::FromStr;
+
+    #[test]
+    fn checksums() {
+        // example from RFC 1071
+        assert_eq!(
+            checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]),
+            !0xddf2
+        );
+        let packet = udp_packet(
+            "192.0.2.1:1024".parse().unwrap(),
+            "192.0.2.2:53".parse().unwrap(),
+            &[1, 2, 3],
+        )
+        .unwrap();
+        assert_eq!(checksum(&packet[..20]), 0);
+        assert!(udp_packet(
+            "192.0.2.1:1024".parse().unwrap(),
+            "[2001:db8::2]:53".parse().unwrap(),
+            &[]
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn exchange() {
+        let mut query = MessageBuilder::new_vec().question();
+        query
+            .push((
+                Dname::<Vec<u8>>::from_str("example.com.").unwrap(),
+                Rtype::A,
+            ))
+            .unwrap();
+        let query = query.finish();
+        let mut response = MessageBuilder::new_vec();
+        response.header_mut().set_qr(true);
+        let response = response.finish();
+        let malformed = vec![0xde, 0xad];
+
+        let servers: Vec<SocketAddr> = vec![
+            "192.0.2.53:53".parse().unwrap(),
+            "[2001:db8::53]:5353".parse().unwrap(),
+            "192.0.2.54:53".parse().unwrap(),
+            "192.0.2.55:53".parse().unwrap(),
+        ];
+        let responses = vec![
+            RawResponse::Data {
+                delay: Duration::from_millis(20),
+                wire: response.clone(),
+            },
+            RawResponse::Data {
+                delay: Duration::from_millis(10),
+                wire: response,
+            },
+            RawResponse::Data {
+                delay: Duration::from_millis(30),
+                wire: malformed.clone(),
+            },
+            RawResponse::Timeout,
+        ];
+        let read = |meta: &[ResponseMeta]| {
+            let mut out = PcapWriter::new(Vec::new(), LINKTYPE_RAW).unwrap();
+            let start = Duration::from_secs(1_000_000);
+            write_exchange(&mut out, 7, &query, &servers, &responses, meta, start).unwrap();
+            let file = out.into_inner().unwrap();
+            PcapReader::new(file.as_slice())
+                .unwrap()
+                .map(|p| p.unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        let packets = read(&[]);
+        assert_eq!(packets.len(), 7);
+        assert_eq!(packets[4].data[0] >> 4, 6); // fastest response first
+        assert!(packets[6].data.ends_with(&malformed));
+
+        // queries can be read back, except for the one sent to a non-standard port
+        let mut collector = QueryCollector::new(53, false);
+        for packet in &packets {
+            collector.add_packet(packet);
+        }
+        assert_eq!(
+            collector.finish(),
+            vec![query.clone(), query.clone(), query.clone()]
+        );
+
+        // send times are used if known
+        let meta: Vec<_> = [2_000_000_000, 1_000_000_000, 0, 3_000_000_000]
+            .into_iter()
+            .map(|sent_at| ResponseMeta {
+                sent_at,
+                ..Default::default()
+            })
+            .collect();
+        let packets = read(&meta);
+        assert_eq!(packets.len(), 7);
+        // server with the earliest send time first, server without send time at start
+        assert_eq!(packets[0].data[0] >> 4, 6);
+        assert_eq!(packets[1].data[0] >> 4, 6);
+        assert_eq!(packets[2].data[16..20], [192, 0, 2, 53]);
+        assert_eq!(packets[4].data[16..20], [192, 0, 2, 55]);
+        assert_eq!(packets[5].data[16..20], [192, 0, 2, 54]);
+        assert!(packets[6].data.ends_with(&malformed));
+    }
+}
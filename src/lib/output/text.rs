@@ -0,0 +1,158 @@
+use crate::{
+    config::FieldWeight,
+    dataformat::{Report, Summary},
+    output::{percentage, top_mismatches, weighted_fields},
+    sumstat::Outlier,
+};
+use std::io::{Result, Write};
+
+/// Number of example queries listed for each mismatch.
+const EXAMPLE_QUERIES: usize = 5;
+
+/// Write a diffsum-style text table.
+///
+/// At most `limit` mismatches with the most queries are listed for each field.
+pub fn write_text(
+    out: &mut impl Write,
+    report: &Report,
+    summary: &Summary,
+    field_weights: &[FieldWeight],
+    limit: usize,
+) -> Result<()> {
+    writeln!(out, "== Global statistics")?;
+    writeln!(
+        out,
+        "{:<40} {:>10} s",
+        "duration",
+        report.end_time.saturating_sub(report.start_time)
+    )?;
+    writeln!(out, "{:<40} {:>10}", "queries", report.total_queries)?;
+    writeln!(
+        out,
+        "{:<40} {:>10} {:>8.2} % of queries",
+        "answers",
+        report.total_answers,
+        percentage(report.total_answers, report.total_queries)
+    )?;
+
+    let disagrees = summary.total();
+    writeln!(out, "\n== Differences statistics")?;
+    writeln!(
+        out,
+        "{:<40} {:>10} {:>8.2} % of answers (ignoring)",
+        "upstream unstable",
+        summary.upstream_unstable,
+        percentage(summary.upstream_unstable, report.total_answers)
+    )?;
+    writeln!(
+        out,
+        "{:<40} {:>10} {:>8.2} % of answers (ignoring)",
+        "not 100% reproducible",
+        summary.not_reproducible,
+        percentage(summary.not_reproducible, report.total_answers)
+    )?;
+    writeln!(
+        out,
+        "{:<40} {:>10} {:>8.2} % of usable answers",
+        "target disagrees",
+        disagrees,
+        percentage(disagrees, summary.usable_answers)
+    )?;
+
+    let fields = weighted_fields(summary, field_weights);
+    writeln!(out, "\n== Target disagreements")?;
+    writeln!(out, "{:<40} {:>10} {:>8}", "== Field", "count", "%")?;
+    for (field, fdis) in &fields {
+        writeln!(
+            out,
+            "{:<40} {:>10} {:>8.2}",
+            field.to_string(),
+            fdis.count(),
+            percentage(fdis.count(), disagrees)
+        )?;
+    }
+
+    for (field, fdis) in &fields {
+        writeln!(
+            out,
+            "\n== {} mismatches (top {} of {})",
+            field,
+            limit.min(fdis.mismatches().len()),
+            fdis.mismatches().len()
+        )?;
+        for mmqueries in top_mismatches(fdis, limit) {
+            let count = mmqueries.queries.len() as u64;
+            writeln!(
+                out,
+                "{:<40} {:>10} {:>8.2}",
+                format!("{} != {}", mmqueries.exp_val, mmqueries.got_val),
+                count,
+                percentage(count, fdis.count())
+            )?;
+            let examples: Vec<_> = mmqueries
+                .queries
+                .iter()
+                .take(EXAMPLE_QUERIES)
+                .map(|key| key.to_string())
+                .collect();
+            writeln!(out, "    e.g. QKeys: {}", examples.join(", "))?;
+        }
+    }
+    Ok(())
+}
+
+/// Write counts which lie outside of the range of reference statistics.
+pub fn write_outliers(out: &mut impl Write, outliers: &[Outlier]) -> Result<()> {
+    writeln!(out, "\n== Outliers compared to reference statistics")?;
+    writeln!(
+        out,
+        "{:<40} {:>10} {:>10} {:>10} {:>8}",
+        "== Counter", "count", "ref min", "ref max", "rank %"
+    )?;
+    for outlier in outliers {
+        writeln!(
+            out,
+            "{:<40} {:>10} {:>10} {:>10} {:>8.2}",
+            outlier.name,
+            outlier.count,
+            outlier.min,
+            outlier.max,
+            outlier.percentile_rank * 100.0
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::{Field, FieldMismatches, Mismatch};
+    use domain::base::iana::rcode::Rcode;
+
+    #[test]
+    fn text_table() {
+        let mut fmismatches = FieldMismatches::new();
+        fmismatches.insert(
+            Mismatch::Rcode(Rcode::NoError, Rcode::ServFail),
+            [1, 2, 3].iter().cloned().collect(),
+        );
+        fmismatches.insert(
+            Mismatch::Rcode(Rcode::NXDomain, Rcode::NoError),
+            [4].iter().cloned().collect(),
+        );
+        let mut report = Report::new();
+        report.total_queries = 10;
+        report.total_answers = 10;
+        report.set_target_disagrees([(Field::Rcode, fmismatches)].into_iter().collect());
+        let weights = [FieldWeight::Timeout, FieldWeight::Rcode];
+        let summary = Summary::from_report(&report, &weights, None);
+
+        let mut out = Vec::new();
+        write_text(&mut out, &report, &summary, &weights, 1).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("== rcode mismatches (top 1 of 2)"));
+        assert!(text.contains("NOERROR != SERVFAIL"));
+        assert!(!text.contains("NXDOMAIN != NOERROR"));
+        assert!(text.contains("e.g. QKeys: 1, 2, 3"));
+    }
+}
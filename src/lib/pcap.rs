@@ -0,0 +1,368 @@
+use crate::error::Error;
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// BSD loopback encapsulation.
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+/// Raw IPv4 or IPv6 packets.
+pub const LINKTYPE_RAW: u32 = 101;
+/// Linux "cooked" capture (``any`` interface).
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+pub const LINKTYPE_IPV4: u32 = 228;
+pub const LINKTYPE_IPV6: u32 = 229;
+
+/// Captured packets larger than this are considered a sign of a corrupted file.
+const MAX_PACKET_LEN: usize = 64 * 1024 * 1024;
+
+/// Section header block type, a palindrome readable before byte order is known.
+const PCAPNG_SHB: u32 = 0x0a0d_0d0a;
+const PCAPNG_IDB: u32 = 1;
+const PCAPNG_SPB: u32 = 3;
+const PCAPNG_EPB: u32 = 6;
+
+/// Packet captured in a pcap or pcapng file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    /// Link-layer header type (``LINKTYPE_*``).
+    pub linktype: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+enum Format {
+    Pcap { linktype: u32 },
+    PcapNg { linktypes: Vec<u32> },
+}
+
+/// Reader of packets from pcap or pcapng files, the format is detected automatically.
+#[derive(Debug)]
+pub struct PcapReader<R> {
+    reader: R,
+    big_endian: bool,
+    format: Format,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Read the file header.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic).map_err(Error::PcapRead)?;
+        let big_endian = match magic {
+            [0xd4, 0xc3, 0xb2, 0xa1] | [0x4d, 0x3c, 0xb2, 0xa1] => false,
+            [0xa1, 0xb2, 0xc3, 0xd4] | [0xa1, 0xb2, 0x3c, 0x4d] => true,
+            [0x0a, 0x0d, 0x0d, 0x0a] => {
+                let mut pcapng = PcapReader {
+                    reader,
+                    big_endian: false,
+                    format: Format::PcapNg {
+                        linktypes: Vec::new(),
+                    },
+                };
+                pcapng.read_section_header()?;
+                return Ok(pcapng);
+            }
+            _ => return Err(Error::Pcap("unknown file format".to_string())),
+        };
+
+        let mut header = [0; 20];
+        reader.read_exact(&mut header).map_err(Error::PcapRead)?;
+        let mut pcap = PcapReader {
+            reader,
+            big_endian,
+            format: Format::Pcap { linktype: 0 },
+        };
+        pcap.format = Format::Pcap {
+            linktype: pcap.u32(&header[16..20]),
+        };
+        Ok(pcap)
+    }
+
+    fn u16(&self, buf: &[u8]) -> u16 {
+        if self.big_endian {
+            BigEndian::read_u16(buf)
+        } else {
+            LittleEndian::read_u16(buf)
+        }
+    }
+
+    fn u32(&self, buf: &[u8]) -> u32 {
+        if self.big_endian {
+            BigEndian::read_u32(buf)
+        } else {
+            LittleEndian::read_u32(buf)
+        }
+    }
+
+    /// Fill the buffer, or return false on clean end of file.
+    fn read_or_eof(&mut self, buf: &mut [u8]) -> Result<bool, Error> {
+        let mut read = 0;
+        while read < buf.len() {
+            match self.reader.read(&mut buf[read..]) {
+                Ok(0) if read == 0 => return Ok(false),
+                Ok(0) => return Err(Error::Pcap("truncated file".to_string())),
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::PcapRead(e)),
+            }
+        }
+        Ok(true)
+    }
+
+    fn read_vec(&mut self, len: usize) -> Result<Vec<u8>, Error> {
+        if len > MAX_PACKET_LEN {
+            return Err(Error::Pcap(format!("record too large ({} bytes)", len)));
+        }
+        let mut buf = vec![0; len];
+        self.reader.read_exact(&mut buf).map_err(Error::PcapRead)?;
+        Ok(buf)
+    }
+
+    /// Read the rest of pcapng section header block after its block type.
+    fn read_section_header(&mut self) -> Result<(), Error> {
+        let mut buf = [0; 8];
+        self.reader.read_exact(&mut buf).map_err(Error::PcapRead)?;
+        self.big_endian = match buf[4..8] {
+            [0x4d, 0x3c, 0x2b, 0x1a] => false,
+            [0x1a, 0x2b, 0x3c, 0x4d] => true,
+            _ => return Err(Error::Pcap("invalid pcapng byte-order magic".to_string())),
+        };
+        let len = self.u32(&buf[0..4]) as usize;
+        if len < 12 {
+            return Err(Error::Pcap("invalid pcapng block length".to_string()));
+        }
+        self.read_vec(len - 12)?;
+        self.format = Format::PcapNg {
+            linktypes: Vec::new(),
+        };
+        Ok(())
+    }
+
+    fn next_pcap(&mut self, linktype: u32) -> Result<Option<Packet>, Error> {
+        let mut header = [0; 16];
+        if !self.read_or_eof(&mut header)? {
+            return Ok(None);
+        }
+        let len = self.u32(&header[8..12]) as usize;
+        let data = self.read_vec(len)?;
+        Ok(Some(Packet { linktype, data }))
+    }
+
+    fn next_pcapng(&mut self) -> Result<Option<Packet>, Error> {
+        loop {
+            let mut header = [0; 8];
+            if !self.read_or_eof(&mut header[..4])? {
+                return Ok(None);
+            }
+            // section header defines byte order, so its length must be read after that
+            if header[..4] == PCAPNG_SHB.to_be_bytes() {
+                self.read_section_header()?;
+                continue;
+            }
+            self.reader
+                .read_exact(&mut header[4..])
+                .map_err(Error::PcapRead)?;
+            let block_type = self.u32(&header[0..4]);
+            let len = self.u32(&header[4..8]) as usize;
+            if len < 12 {
+                return Err(Error::Pcap("invalid pcapng block length".to_string()));
+            }
+            let mut body = self.read_vec(len - 8)?;
+            body.truncate(len - 12);
+
+            let (iface, data) = match block_type {
+                PCAPNG_IDB if body.len() >= 2 => {
+                    let linktype = self.u16(&body[0..2]) as u32;
+                    if let Format::PcapNg { linktypes } = &mut self.format {
+                        linktypes.push(linktype);
+                    }
+                    continue;
+                }
+                PCAPNG_EPB if body.len() >= 20 => {
+                    let iface = self.u32(&body[0..4]) as usize;
+                    let caplen = self.u32(&body[12..16]) as usize;
+                    (iface, body.get(20..20 + caplen))
+                }
+                PCAPNG_SPB if body.len() >= 4 => {
+                    let origlen = self.u32(&body[0..4]) as usize;
+                    (0, body.get(4..4 + origlen.min(body.len() - 4)))
+                }
+                _ => continue,
+            };
+            let linktype = match &self.format {
+                Format::PcapNg { linktypes } => linktypes.get(iface).copied(),
+                Format::Pcap { .. } => unreachable!(),
+            };
+            return match (linktype, data) {
+                (Some(linktype), Some(data)) => Ok(Some(Packet {
+                    linktype,
+                    data: data.to_vec(),
+                })),
+                (None, _) => Err(Error::Pcap(format!(
+                    "packet on unknown interface {}",
+                    iface
+                ))),
+                (_, None) => Err(Error::Pcap("truncated packet block".to_string())),
+            };
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<Packet, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let packet = match self.format {
+            Format::Pcap { linktype } => self.next_pcap(linktype),
+            Format::PcapNg { .. } => self.next_pcapng(),
+        };
+        packet.transpose()
+    }
+}
+
+/// Writer of packets to pcap files with microsecond timestamps.
+#[derive(Debug)]
+pub struct PcapWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Write the file header for packets with the given link-layer header type.
+    pub fn new(mut writer: W, linktype: u32) -> io::Result<Self> {
+        let mut header = [0; 24];
+        LittleEndian::write_u32(&mut header[0..4], 0xa1b2_c3d4);
+        LittleEndian::write_u16(&mut header[4..6], 2);
+        LittleEndian::write_u16(&mut header[6..8], 4);
+        LittleEndian::write_u32(&mut header[16..20], u16::MAX as u32);
+        LittleEndian::write_u32(&mut header[20..24], linktype);
+        writer.write_all(&header)?;
+        Ok(PcapWriter { writer })
+    }
+
+    /// Write a packet captured at the time since Unix epoch.
+    pub fn write_packet(&mut self, time: Duration, data: &[u8]) -> io::Result<()> {
+        let mut header = [0; 16];
+        LittleEndian::write_u32(&mut header[0..4], time.as_secs() as u32);
+        LittleEndian::write_u32(&mut header[4..8], time.subsec_micros());
+        LittleEndian::write_u32(&mut header[8..12], data.len() as u32);
+        LittleEndian::write_u32(&mut header[12..16], data.len() as u32);
+        self.writer.write_all(&header)?;
+        self.writer.write_all(data)
+    }
+
+    /// Flush and return the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_pcap() {
+        let mut file = vec![
+            0xd4, 0xc3, 0xb2, 0xa1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0xff, 0xff, 0x00, 0x00, 0x65, 0x00, 0x00, 0x00, // raw IP
+        ];
+        for data in [&[1u8, 2, 3][..], &[4]] {
+            file.extend([0; 8]);
+            file.extend((data.len() as u32).to_le_bytes());
+            file.extend((data.len() as u32).to_le_bytes());
+            file.extend(data);
+        }
+        let packets: Vec<_> = PcapReader::new(file.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            packets,
+            vec![
+                Packet {
+                    linktype: LINKTYPE_RAW,
+                    data: vec![1, 2, 3]
+                },
+                Packet {
+                    linktype: LINKTYPE_RAW,
+                    data: vec![4]
+                },
+            ]
+        );
+
+        file.pop();
+        let mut reader = PcapReader::new(file.as_slice()).unwrap();
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+
+        assert!(PcapReader::new(&b"nope"[..]).is_err());
+    }
+
+    #[test]
+    fn read_pcapng() {
+        fn block(block_type: u32, body: &[u8]) -> Vec<u8> {
+            let padded = body.len().next_multiple_of(4);
+            let len = (padded + 12) as u32;
+            let mut block = Vec::new();
+            block.extend(block_type.to_be_bytes());
+            block.extend(len.to_be_bytes());
+            block.extend(body);
+            block.resize(8 + padded, 0);
+            block.extend(len.to_be_bytes());
+            block
+        }
+
+        let mut file = block(
+            PCAPNG_SHB,
+            &[
+                0x1a, 0x2b, 0x3c, 0x4d, 0, 1, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ],
+        );
+        file.extend(block(PCAPNG_IDB, &[0, 1, 0, 0, 0, 0, 0, 0])); // ethernet
+        file.extend(block(0xbad, &[1, 2, 3, 4]));
+        let mut epb = vec![0; 12];
+        epb.extend(3u32.to_be_bytes());
+        epb.extend(3u32.to_be_bytes());
+        epb.extend([7, 8, 9]);
+        file.extend(block(PCAPNG_EPB, &epb));
+        file.extend(block(PCAPNG_SPB, &[0, 0, 0, 2, 5, 6]));
+
+        let packets: Vec<_> = PcapReader::new(file.as_slice())
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            packets,
+            vec![
+                Packet {
+                    linktype: LINKTYPE_ETHERNET,
+                    data: vec![7, 8, 9]
+                },
+                Packet {
+                    linktype: LINKTYPE_ETHERNET,
+                    data: vec![5, 6]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn write_pcap() {
+        let mut writer = PcapWriter::new(Vec::new(), LINKTYPE_RAW).unwrap();
+        writer
+            .write_packet(Duration::from_micros(1_500_000), &[1, 2])
+            .unwrap();
+        writer.write_packet(Duration::ZERO, &[]).unwrap();
+        let file = writer.into_inner().unwrap();
+        assert_eq!(&file[24..28], &1u32.to_le_bytes());
+        assert_eq!(&file[28..32], &500_000u32.to_le_bytes());
+
+        let packets: Vec<_> = PcapReader::new(file.as_slice())
+            .unwrap()
+            .map(|p| p.unwrap().data)
+            .collect();
+        assert_eq!(packets, vec![vec![1, 2], vec![]]);
+    }
+}
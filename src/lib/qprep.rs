@@ -0,0 +1,513 @@
+use crate::error::Error;
+use crate::pcap::{
+    Packet, LINKTYPE_ETHERNET, LINKTYPE_IPV4, LINKTYPE_IPV6, LINKTYPE_LINUX_SLL, LINKTYPE_NULL,
+    LINKTYPE_RAW,
+};
+use byteorder::{BigEndian, ByteOrder};
+use domain::base::{
+    iana::{Class, Rtype},
+    name::{Dname, ToDname},
+    Message, MessageBuilder,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// EDNS buffer size of queries with EDNS, unless set explicitly.
+pub const DEFAULT_BUFSIZE: u16 = 1232;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+
+/// Source and destination address and port of a TCP connection.
+type Flow = (IpAddr, u16, IpAddr, u16);
+
+/// Data sent in one direction of a TCP connection.
+#[derive(Debug, Default)]
+struct TcpStream {
+    /// Order in which the streams were seen, so that remaining streams are flushed
+    /// deterministically.
+    id: u64,
+    /// Sequence number of the first byte of the stream.
+    base: Option<u32>,
+    /// Segment data by their offset from `base`.
+    segments: BTreeMap<u32, Vec<u8>>,
+}
+
+impl TcpStream {
+    fn add(&mut self, seq: u32, flags: u8, payload: &[u8]) {
+        let seq = if flags & TCP_SYN != 0 {
+            let isn = seq.wrapping_add(1);
+            self.base = Some(isn);
+            isn
+        } else {
+            seq
+        };
+        if payload.is_empty() {
+            return;
+        }
+        let base = *self.base.get_or_insert(seq);
+        let offset = seq.wrapping_sub(base);
+        if offset > i32::MAX as u32 {
+            return; // data before the start of stream, when SYN wasn't captured
+        }
+        let segment = self.segments.entry(offset).or_default();
+        if payload.len() > segment.len() {
+            *segment = payload.to_vec();
+        }
+    }
+
+    /// Return DNS messages from the contiguous beginning of the stream.
+    fn messages(self) -> Vec<Vec<u8>> {
+        let mut stream = Vec::new();
+        for (offset, data) in self.segments {
+            let offset = offset as usize;
+            if offset > stream.len() {
+                break; // missing segment
+            }
+            if offset + data.len() > stream.len() {
+                stream.extend_from_slice(&data[stream.len() - offset..]);
+            }
+        }
+
+        let mut messages = Vec::new();
+        let mut rest = stream.as_slice();
+        while rest.len() >= 2 {
+            let len = BigEndian::read_u16(rest) as usize;
+            if rest.len() < 2 + len {
+                break;
+            }
+            messages.push(rest[2..2 + len].to_vec());
+            rest = &rest[2 + len..];
+        }
+        messages
+    }
+}
+
+/// Collector of DNS queries, which are optionally deduplicated.
+///
+/// Queries are kept in the order in which they were sent, except for queries over TCP, which are
+/// collected once their connection is closed.
+#[derive(Debug)]
+pub struct QueryCollector {
+    port: u16,
+    seen: Option<HashSet<(String, Rtype, Class)>>,
+    streams: HashMap<Flow, TcpStream>,
+    next_stream: u64,
+    queries: Vec<Vec<u8>>,
+}
+
+impl QueryCollector {
+    /// Create a collector of queries sent to the port.
+    ///
+    /// With `dedup`, only the first query with the same qname (compared case-insensitively),
+    /// qtype and qclass is kept.
+    pub fn new(port: u16, dedup: bool) -> Self {
+        QueryCollector {
+            port,
+            seen: dedup.then(HashSet::new),
+            streams: HashMap::new(),
+            next_stream: 0,
+            queries: Vec::new(),
+        }
+    }
+
+    /// Add a DNS message, return whether it was kept.
+    ///
+    /// Messages which aren't queries with a question are ignored, as are duplicates.
+    pub fn add_query(&mut self, wire: Vec<u8>) -> bool {
+        let key = match Message::from_octets(wire.as_slice()) {
+            Ok(msg) if !msg.header().qr() => match msg.question().next() {
+                Some(Ok(q)) => (
+                    q.qname().to_string().to_ascii_lowercase(),
+                    q.qtype(),
+                    q.qclass(),
+                ),
+                _ => return false,
+            },
+            _ => return false,
+        };
+        if let Some(seen) = &mut self.seen {
+            if !seen.insert(key) {
+                return false;
+            }
+        }
+        self.queries.push(wire);
+        true
+    }
+
+    /// Extract DNS queries from a captured packet.
+    ///
+    /// Packets with unsupported link types and IP fragments are ignored.
+    pub fn add_packet(&mut self, packet: &Packet) {
+        let ip = match ip_packet(packet.linktype, &packet.data) {
+            Some(ip) => ip,
+            None => return,
+        };
+        let (src, dst, proto, data) = match transport(ip) {
+            Some(transport) => transport,
+            None => return,
+        };
+        match proto {
+            IPPROTO_UDP if data.len() >= 8 => {
+                if BigEndian::read_u16(&data[2..4]) != self.port {
+                    return;
+                }
+                let len = (BigEndian::read_u16(&data[4..6]) as usize).clamp(8, data.len());
+                self.add_query(data[8..len].to_vec());
+            }
+            IPPROTO_TCP if data.len() >= 20 => {
+                let sport = BigEndian::read_u16(&data[0..2]);
+                let dport = BigEndian::read_u16(&data[2..4]);
+                if dport != self.port {
+                    return;
+                }
+                let seq = BigEndian::read_u32(&data[4..8]);
+                let offset = (data[12] >> 4) as usize * 4;
+                let flags = data[13];
+                let payload = data.get(offset..).unwrap_or_default();
+
+                let flow = (src, sport, dst, dport);
+                if flags & TCP_SYN != 0 {
+                    // connection reusing the same ports
+                    self.flush(&flow);
+                }
+                let next_stream = &mut self.next_stream;
+                self.streams
+                    .entry(flow)
+                    .or_insert_with(|| {
+                        *next_stream += 1;
+                        TcpStream {
+                            id: *next_stream,
+                            ..Default::default()
+                        }
+                    })
+                    .add(seq, flags, payload);
+                if flags & (TCP_FIN | TCP_RST) != 0 {
+                    self.flush(&flow);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn flush(&mut self, flow: &Flow) {
+        if let Some(stream) = self.streams.remove(flow) {
+            for wire in stream.messages() {
+                self.add_query(wire);
+            }
+        }
+    }
+
+    /// Collect queries from unfinished TCP connections and return all queries.
+    pub fn finish(mut self) -> Vec<Vec<u8>> {
+        let mut streams: Vec<_> = self.streams.drain().map(|(_, stream)| stream).collect();
+        streams.sort_by_key(|stream| stream.id);
+        for stream in streams {
+            for wire in stream.messages() {
+                self.add_query(wire);
+            }
+        }
+        self.queries
+    }
+}
+
+/// Header flags and EDNS parameters of generated queries.
+///
+/// EDNS is used when the DO bit or buffer size is set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryOptions {
+    pub rd: bool,
+    /// DNSSEC OK bit.
+    pub dnssec: bool,
+    pub cd: bool,
+    /// EDNS buffer size, `DEFAULT_BUFSIZE` is used if only the DO bit is set.
+    pub bufsize: Option<u16>,
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            rd: true,
+            dnssec: false,
+            cd: false,
+            bufsize: None,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// Apply an option like ``+dnssec``, ``+nord`` or ``+bufsize=4096`` from a text list line.
+    fn apply(&mut self, option: &str) -> Result<(), Error> {
+        match option {
+            "+rd" => self.rd = true,
+            "+nord" => self.rd = false,
+            "+dnssec" | "+do" => self.dnssec = true,
+            "+nodnssec" | "+nodo" => self.dnssec = false,
+            "+cd" => self.cd = true,
+            "+nocd" => self.cd = false,
+            "+nobufsize" => self.bufsize = None,
+            _ => match option.strip_prefix("+bufsize=").map(u16::from_str) {
+                Some(Ok(bufsize)) => self.bufsize = Some(bufsize),
+                _ => return Err(Error::InvalidQuery(format!("unknown option {}", option))),
+            },
+        }
+        Ok(())
+    }
+
+    /// Build a query for the question in wire format.
+    pub fn build_query<N: ToDname>(&self, qname: N, qtype: Rtype) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_rd(self.rd);
+        msg.header_mut().set_cd(self.cd);
+        let mut msg = msg.question();
+        msg.push((qname, qtype))
+            .expect("vec can't run out of space");
+        if !self.dnssec && self.bufsize.is_none() {
+            return msg.finish();
+        }
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_udp_payload_size(self.bufsize.unwrap_or(DEFAULT_BUFSIZE));
+            opt.set_dnssec_ok(self.dnssec);
+            Ok(())
+        })
+        .expect("vec can't run out of space");
+        msg.finish()
+    }
+}
+
+/// Build a query from a text list line like ``example.com. AAAA +dnssec +cd``.
+///
+/// Options on the line override the given defaults. Returns `None` for empty lines and comments
+/// starting with ``#`` or ``;``.
+pub fn parse_query_line(line: &str, defaults: &QueryOptions) -> Result<Option<Vec<u8>>, Error> {
+    let mut tokens = line.split_whitespace();
+    let qname = match tokens.next() {
+        None => return Ok(None),
+        Some(token) if token.starts_with('#') || token.starts_with(';') => return Ok(None),
+        Some(token) => Dname::<Vec<u8>>::from_str(token)
+            .map_err(|e| Error::InvalidQuery(format!("invalid qname {}: {}", token, e)))?,
+    };
+    let qtype = match tokens.next() {
+        Some(token) => Rtype::from_str(token)
+            .map_err(|_| Error::InvalidQuery(format!("invalid qtype {}", token)))?,
+        None => return Err(Error::InvalidQuery("missing qtype".to_string())),
+    };
+    let mut options = defaults.clone();
+    for option in tokens {
+        options.apply(option)?;
+    }
+    Ok(Some(options.build_query(qname, qtype)))
+}
+
+/// Strip the link-layer header and return the IP packet.
+fn ip_packet(linktype: u32, data: &[u8]) -> Option<&[u8]> {
+    match linktype {
+        LINKTYPE_NULL => data.get(4..),
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            loop {
+                match BigEndian::read_u16(data.get(offset..offset + 2)?) {
+                    0x0800 | 0x86dd => return data.get(offset + 2..),
+                    // 802.1Q and 802.1ad VLAN tags
+                    0x8100 | 0x88a8 => offset += 4,
+                    _ => return None,
+                }
+            }
+        }
+        LINKTYPE_LINUX_SLL => match BigEndian::read_u16(data.get(14..16)?) {
+            0x0800 | 0x86dd => data.get(16..),
+            _ => None,
+        },
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(data),
+        _ => None,
+    }
+}
+
+/// Parse IPv4 or IPv6 header, return addresses, transport protocol and its data.
+fn transport(ip: &[u8]) -> Option<(IpAddr, IpAddr, u8, &[u8])> {
+    match ip.first()? >> 4 {
+        4 if ip.len() >= 20 => {
+            let header_len = (ip[0] & 0x0f) as usize * 4;
+            let total_len = BigEndian::read_u16(&ip[2..4]) as usize;
+            // more fragments flag or fragment offset
+            if BigEndian::read_u16(&ip[6..8]) & 0x3fff != 0 {
+                return None;
+            }
+            let src = <[u8; 4]>::try_from(&ip[12..16]).ok()?;
+            let dst = <[u8; 4]>::try_from(&ip[16..20]).ok()?;
+            let data = ip.get(header_len..total_len.min(ip.len()))?;
+            Some((src.into(), dst.into(), ip[9], data))
+        }
+        6 if ip.len() >= 40 => {
+            let payload_len = BigEndian::read_u16(&ip[4..6]) as usize;
+            let src = <[u8; 16]>::try_from(&ip[8..24]).ok()?;
+            let dst = <[u8; 16]>::try_from(&ip[24..40]).ok()?;
+            let mut data = ip.get(40..(40 + payload_len).min(ip.len()))?;
+            let mut next = ip[6];
+            // hop-by-hop, routing and destination options extension headers
+            while let 0 | 43 | 60 = next {
+                next = *data.first()?;
+                let len = (*data.get(1)? as usize + 1) * 8;
+                data = data.get(len..)?;
+            }
+            Some((src.into(), dst.into(), next, data))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(qname: &str, qtype: Rtype, response: bool) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec();
+        msg.header_mut().set_qr(response);
+        let mut msg = msg.question();
+        msg.push((Dname::vec_from_str(qname).unwrap(), qtype))
+            .unwrap();
+        msg.finish()
+    }
+
+    fn udp4_ethernet(dport: u16, payload: &[u8]) -> Packet {
+        let mut data = vec![0; 12];
+        data.extend([0x08, 0x00]);
+        data.extend([0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, IPPROTO_UDP, 0, 0]);
+        data[16..18].copy_from_slice(&(28 + payload.len() as u16).to_be_bytes());
+        data.extend([192, 0, 2, 1, 192, 0, 2, 53]);
+        data.extend(1234u16.to_be_bytes());
+        data.extend(dport.to_be_bytes());
+        data.extend((8 + payload.len() as u16).to_be_bytes());
+        data.extend([0, 0]);
+        data.extend(payload);
+        Packet {
+            linktype: LINKTYPE_ETHERNET,
+            data,
+        }
+    }
+
+    fn tcp6_raw(seq: u32, flags: u8, payload: &[u8]) -> Packet {
+        let mut data = vec![0x60, 0, 0, 0];
+        data.extend((20 + payload.len() as u16).to_be_bytes());
+        data.extend([IPPROTO_TCP, 64]);
+        data.extend([0x20, 0x01, 0x0d, 0xb8].iter().chain(&[0; 11]).chain(&[1]));
+        data.extend([0x20, 0x01, 0x0d, 0xb8].iter().chain(&[0; 11]).chain(&[2]));
+        data.extend(1234u16.to_be_bytes());
+        data.extend(53u16.to_be_bytes());
+        data.extend(seq.to_be_bytes());
+        data.extend([0, 0, 0, 0, 0x50, flags, 0, 0, 0, 0, 0, 0]);
+        data.extend(payload);
+        Packet {
+            linktype: LINKTYPE_RAW,
+            data,
+        }
+    }
+
+    #[test]
+    fn udp() {
+        let query = message("example.com.", Rtype::A, false);
+        let mut collector = QueryCollector::new(53, false);
+        collector.add_packet(&udp4_ethernet(53, &query));
+        collector.add_packet(&udp4_ethernet(5353, &query));
+        collector.add_packet(&udp4_ethernet(53, &message("example.com.", Rtype::A, true)));
+        collector.add_packet(&udp4_ethernet(53, b"garbage"));
+        collector.add_packet(&udp4_ethernet(53, &query));
+        assert_eq!(collector.finish(), vec![query.clone(), query]);
+    }
+
+    #[test]
+    fn tcp_reassembly() {
+        let q1 = message("a.example.", Rtype::A, false);
+        let q2 = message("b.example.", Rtype::Aaaa, false);
+        let mut stream = Vec::new();
+        for q in [&q1, &q2] {
+            stream.extend((q.len() as u16).to_be_bytes());
+            stream.extend(q);
+        }
+        let isn = u32::MAX - 5; // sequence numbers wrap around
+        let start = isn.wrapping_add(1);
+        let split = q1.len() + 5;
+
+        let mut collector = QueryCollector::new(53, false);
+        collector.add_packet(&tcp6_raw(isn, TCP_SYN, &[]));
+        // out of order and retransmitted segments
+        collector.add_packet(&tcp6_raw(
+            start.wrapping_add(split as u32),
+            0,
+            &stream[split..],
+        ));
+        collector.add_packet(&tcp6_raw(start, 0, &stream[..3]));
+        collector.add_packet(&tcp6_raw(start, 0, &stream[..split]));
+        assert!(collector.queries.is_empty());
+        collector.add_packet(&tcp6_raw(
+            start.wrapping_add(stream.len() as u32),
+            TCP_FIN,
+            &[],
+        ));
+        assert_eq!(collector.queries, vec![q1.clone(), q2.clone()]);
+
+        // unfinished connection without captured handshake
+        collector.add_packet(&tcp6_raw(1000, 0, &stream[..split]));
+        assert_eq!(collector.finish(), vec![q1.clone(), q2, q1]);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut collector = QueryCollector::new(53, true);
+        assert!(collector.add_query(message("example.com.", Rtype::A, false)));
+        assert!(!collector.add_query(message("EXAMPLE.com.", Rtype::A, false)));
+        assert!(collector.add_query(message("example.com.", Rtype::Aaaa, false)));
+        assert_eq!(collector.finish().len(), 2);
+    }
+
+    #[test]
+    fn query_line() {
+        let defaults = QueryOptions::default();
+        assert_eq!(parse_query_line("", &defaults), Ok(None));
+        assert_eq!(parse_query_line("  # comment", &defaults), Ok(None));
+        assert!(parse_query_line("example.com.", &defaults).is_err());
+        assert!(parse_query_line("example.com. BOGUS", &defaults).is_err());
+        assert!(parse_query_line("example.com. A +bogus", &defaults).is_err());
+
+        let wire = parse_query_line("example.com AAAA", &defaults)
+            .unwrap()
+            .unwrap();
+        let msg = Message::from_octets(wire.as_slice()).unwrap();
+        assert!(msg.header().rd());
+        assert!(!msg.header().cd());
+        assert!(msg.opt().is_none());
+        let q = msg.sole_question().unwrap();
+        assert_eq!(q.qname().to_string(), "example.com");
+        assert_eq!(q.qtype(), Rtype::Aaaa);
+
+        let wire = parse_query_line("example.com. A +dnssec +cd +nord", &defaults)
+            .unwrap()
+            .unwrap();
+        let msg = Message::from_octets(wire.as_slice()).unwrap();
+        assert!(!msg.header().rd());
+        assert!(msg.header().cd());
+        let opt = msg.opt().unwrap();
+        assert!(opt.dnssec_ok());
+        assert_eq!(opt.udp_payload_size(), DEFAULT_BUFSIZE);
+
+        let defaults = QueryOptions {
+            dnssec: true,
+            ..Default::default()
+        };
+        let wire = parse_query_line("example.com. A +nodnssec +bufsize=512", &defaults)
+            .unwrap()
+            .unwrap();
+        let opt = Message::from_octets(wire.as_slice())
+            .unwrap()
+            .opt()
+            .unwrap();
+        assert!(!opt.dnssec_ok());
+        assert_eq!(opt.udp_payload_size(), 512);
+    }
+}
@@ -0,0 +1,265 @@
+use crate::{dataformat::Summary, matcher::Field, QKey};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Mismatch category identified by its field and values.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Category {
+    pub field: Field,
+    pub exp_val: String,
+    pub got_val: String,
+}
+
+/// Kind of change between two counts.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Appeared,
+    Disappeared,
+    Grew,
+    Shrank,
+    Unchanged,
+}
+
+/// Count of queries in the old and the new summary.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CountChange {
+    pub old: u64,
+    pub new: u64,
+}
+
+impl CountChange {
+    /// Return the kind of change.
+    pub fn kind(&self) -> ChangeKind {
+        match (self.old, self.new) {
+            (0, 0) => ChangeKind::Unchanged,
+            (0, _) => ChangeKind::Appeared,
+            (_, 0) => ChangeKind::Disappeared,
+            (old, new) => match new.cmp(&old) {
+                Ordering::Greater => ChangeKind::Grew,
+                Ordering::Less => ChangeKind::Shrank,
+                Ordering::Equal => ChangeKind::Unchanged,
+            },
+        }
+    }
+
+    /// Return the difference between the new and the old count.
+    pub fn diff(&self) -> i64 {
+        self.new as i64 - self.old as i64
+    }
+}
+
+/// Change of count in a single field.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: Field,
+    pub kind: ChangeKind,
+    #[serde(flatten)]
+    pub count: CountChange,
+}
+
+/// Change of count in a single mismatch category.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct MismatchChange {
+    #[serde(flatten)]
+    pub category: Category,
+    pub kind: ChangeKind,
+    #[serde(flatten)]
+    pub count: CountChange,
+}
+
+/// Query which is counted under a different category in the new summary.
+///
+/// `None` means the query had no (counted) mismatch.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct QueryChange {
+    pub key: QKey,
+    pub old: Option<Category>,
+    pub new: Option<Category>,
+}
+
+/// Comparison of summaries from two different runs (sumcmp).
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SummaryComparison {
+    pub upstream_unstable: CountChange,
+    pub usable_answers: CountChange,
+    pub not_reproducible: CountChange,
+    /// Changes in per-field counts, including unchanged fields.
+    pub fields: Vec<FieldChange>,
+    /// Changes in per-mismatch counts, excluding unchanged mismatches.
+    pub mismatches: Vec<MismatchChange>,
+    /// Queries which changed their category.
+    pub queries: Vec<QueryChange>,
+}
+
+impl SummaryComparison {
+    /// Compare the old summary with the new one.
+    pub fn new(old: &Summary, new: &Summary) -> Self {
+        let old_cats = categories(old);
+        let new_cats = categories(new);
+
+        let fields: BTreeSet<Field> = old
+            .fields()
+            .keys()
+            .chain(new.fields().keys())
+            .copied()
+            .collect();
+        let fields = fields
+            .into_iter()
+            .map(|field| {
+                let count = CountChange {
+                    old: old.field_count(field),
+                    new: new.field_count(field),
+                };
+                FieldChange {
+                    field,
+                    kind: count.kind(),
+                    count,
+                }
+            })
+            .collect();
+
+        let mut counts: BTreeMap<&Category, CountChange> = BTreeMap::new();
+        for cat in old_cats.values() {
+            counts
+                .entry(cat)
+                .or_insert(CountChange { old: 0, new: 0 })
+                .old += 1;
+        }
+        for cat in new_cats.values() {
+            counts
+                .entry(cat)
+                .or_insert(CountChange { old: 0, new: 0 })
+                .new += 1;
+        }
+        let mismatches = counts
+            .into_iter()
+            .filter(|(_, count)| count.kind() != ChangeKind::Unchanged)
+            .map(|(cat, count)| MismatchChange {
+                category: cat.clone(),
+                kind: count.kind(),
+                count,
+            })
+            .collect();
+
+        let keys: BTreeSet<QKey> = old_cats.keys().chain(new_cats.keys()).copied().collect();
+        let queries = keys
+            .into_iter()
+            .filter_map(|key| {
+                let old = old_cats.get(&key);
+                let new = new_cats.get(&key);
+                if old == new {
+                    return None;
+                }
+                Some(QueryChange {
+                    key,
+                    old: old.cloned(),
+                    new: new.cloned(),
+                })
+            })
+            .collect();
+
+        SummaryComparison {
+            upstream_unstable: CountChange {
+                old: old.upstream_unstable,
+                new: new.upstream_unstable,
+            },
+            usable_answers: CountChange {
+                old: old.usable_answers,
+                new: new.usable_answers,
+            },
+            not_reproducible: CountChange {
+                old: old.not_reproducible,
+                new: new.not_reproducible,
+            },
+            fields,
+            mismatches,
+            queries,
+        }
+    }
+}
+
+/// Return the category of each query counted in the summary.
+fn categories(summary: &Summary) -> BTreeMap<QKey, Category> {
+    let mut cats = BTreeMap::new();
+    for (field, fdis) in summary.fields() {
+        for mmqueries in fdis.mismatches() {
+            let cat = Category {
+                field: *field,
+                exp_val: mmqueries.exp_val.clone(),
+                got_val: mmqueries.got_val.clone(),
+            };
+            for key in &mmqueries.queries {
+                cats.insert(*key, cat.clone());
+            }
+        }
+    }
+    cats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FieldWeight;
+    use crate::dataformat::Report;
+    use crate::matcher::{FieldMismatches, Mismatch};
+    use domain::base::iana::rcode::Rcode;
+
+    fn summary(rcodes: &[(Rcode, Rcode, &[QKey])]) -> Summary {
+        let mut fmismatches = FieldMismatches::new();
+        for (exp, got, queries) in rcodes {
+            fmismatches.insert(
+                Mismatch::Rcode(*exp, *got),
+                queries.iter().copied().collect(),
+            );
+        }
+        let mut report = Report::new();
+        report.total_answers = 100;
+        report.set_target_disagrees([(Field::Rcode, fmismatches)].into_iter().collect());
+        Summary::from_report(&report, &[FieldWeight::Rcode], None)
+    }
+
+    #[test]
+    fn compare_summaries() {
+        use Rcode::*;
+
+        let old = summary(&[(NoError, ServFail, &[1, 2, 3]), (NXDomain, NoError, &[4])]);
+        let new = summary(&[(NoError, ServFail, &[1, 2]), (NoError, Refused, &[3, 5])]);
+        let cmp = SummaryComparison::new(&old, &new);
+
+        assert_eq!(
+            cmp.fields,
+            vec![FieldChange {
+                field: Field::Rcode,
+                kind: ChangeKind::Unchanged,
+                count: CountChange { old: 4, new: 4 },
+            }]
+        );
+
+        let kinds: Vec<_> = cmp
+            .mismatches
+            .iter()
+            .map(|m| {
+                (
+                    m.category.exp_val.as_str(),
+                    m.category.got_val.as_str(),
+                    m.kind,
+                )
+            })
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("NOERROR", "REFUSED", ChangeKind::Appeared),
+                ("NOERROR", "SERVFAIL", ChangeKind::Shrank),
+                ("NXDOMAIN", "NOERROR", ChangeKind::Disappeared),
+            ]
+        );
+
+        let keys: Vec<_> = cmp.queries.iter().map(|q| q.key).collect();
+        assert_eq!(keys, vec![3, 4, 5]);
+        assert!(cmp.queries[1].new.is_none());
+        assert!(cmp.queries[2].old.is_none());
+    }
+}
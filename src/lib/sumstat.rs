@@ -0,0 +1,375 @@
+use crate::{dataformat::Summary, matcher::Field, sumcmp::Category};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Statistics of a count over multiple runs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Count in each run, in the order of the runs.
+    pub samples: Vec<u64>,
+    pub mean: f64,
+    /// Sample standard deviation, zero for less than two samples.
+    pub stdev: f64,
+    pub min: u64,
+    pub p25: f64,
+    pub median: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub max: u64,
+}
+
+impl Stats {
+    /// Compute statistics of the given samples.
+    pub fn new(samples: Vec<u64>) -> Self {
+        let mut sorted = samples.clone();
+        sorted.sort_unstable();
+
+        let n = samples.len() as f64;
+        let mean = if samples.is_empty() {
+            0.0
+        } else {
+            samples.iter().sum::<u64>() as f64 / n
+        };
+        let stdev = if samples.len() < 2 {
+            0.0
+        } else {
+            let var = samples
+                .iter()
+                .map(|s| (*s as f64 - mean).powi(2))
+                .sum::<f64>()
+                / (n - 1.0);
+            var.sqrt()
+        };
+
+        Stats {
+            mean,
+            stdev,
+            min: sorted.first().copied().unwrap_or(0),
+            p25: percentile(&sorted, 0.25),
+            median: percentile(&sorted, 0.5),
+            p75: percentile(&sorted, 0.75),
+            p95: percentile(&sorted, 0.95),
+            max: sorted.last().copied().unwrap_or(0),
+            samples,
+        }
+    }
+
+    /// Return the fraction of samples which are lower or equal to the value.
+    pub fn percentile_rank(&self, value: u64) -> f64 {
+        if self.samples.is_empty() {
+            return 0.0;
+        }
+        let below = self.samples.iter().filter(|s| **s <= value).count();
+        below as f64 / self.samples.len() as f64
+    }
+
+    /// Check whether the value lies outside of the range of the samples.
+    pub fn is_outlier(&self, value: u64) -> bool {
+        value < self.min || value > self.max
+    }
+}
+
+/// Return the q-th quantile of sorted samples using linear interpolation.
+fn percentile(sorted: &[u64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    let frac = pos - lower as f64;
+    sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * frac
+}
+
+/// Statistics of a single mismatch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MismatchStatistics {
+    pub exp_val: String,
+    pub got_val: String,
+    pub count: Stats,
+}
+
+/// Statistics of a single field.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldStatistics {
+    pub total: Stats,
+    pub mismatches: Vec<MismatchStatistics>,
+}
+
+/// Reference statistics of summaries from repeated runs of the same configuration (sumstat).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SummaryStatistics {
+    pub sample_size: usize,
+    pub upstream_unstable: Stats,
+    pub usable_answers: Stats,
+    pub not_reproducible: Stats,
+    pub target_disagreements: Stats,
+    pub fields: BTreeMap<Field, FieldStatistics>,
+}
+
+impl SummaryStatistics {
+    /// Compute statistics of the given summaries.
+    ///
+    /// Fields and mismatches missing from some summaries are counted as zero in those.
+    pub fn new(summaries: &[Summary]) -> Self {
+        let stats =
+            |count: &dyn Fn(&Summary) -> u64| Stats::new(summaries.iter().map(count).collect());
+
+        let categories: BTreeSet<Category> = summaries
+            .iter()
+            .flat_map(|summary| {
+                summary.fields().iter().flat_map(|(field, fdis)| {
+                    fdis.mismatches().iter().map(|mmqueries| Category {
+                        field: *field,
+                        exp_val: mmqueries.exp_val.clone(),
+                        got_val: mmqueries.got_val.clone(),
+                    })
+                })
+            })
+            .collect();
+
+        let mut fields: BTreeMap<Field, FieldStatistics> = BTreeMap::new();
+        for cat in categories {
+            let count = stats(&|summary| mismatch_count(summary, &cat));
+            fields
+                .entry(cat.field)
+                .or_insert_with(|| FieldStatistics {
+                    total: stats(&|summary| summary.field_count(cat.field)),
+                    mismatches: Vec::new(),
+                })
+                .mismatches
+                .push(MismatchStatistics {
+                    exp_val: cat.exp_val,
+                    got_val: cat.got_val,
+                    count,
+                });
+        }
+
+        SummaryStatistics {
+            sample_size: summaries.len(),
+            upstream_unstable: stats(&|summary| summary.upstream_unstable),
+            usable_answers: stats(&|summary| summary.usable_answers),
+            not_reproducible: stats(&|summary| summary.not_reproducible),
+            target_disagreements: stats(&|summary| summary.total()),
+            fields,
+        }
+    }
+}
+
+/// Count of a summary which lies outside of the range of its reference statistics.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Outlier {
+    /// Name of the counter, a field or a mismatch in the field.
+    pub name: String,
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    /// Fraction of reference samples lower or equal to the count.
+    pub percentile_rank: f64,
+}
+
+impl Outlier {
+    fn check(name: String, stats: &Stats, count: u64) -> Option<Self> {
+        stats.is_outlier(count).then(|| Outlier {
+            name,
+            count,
+            min: stats.min,
+            max: stats.max,
+            percentile_rank: stats.percentile_rank(count),
+        })
+    }
+}
+
+impl SummaryStatistics {
+    /// Return counts of the summary which lie outside of the reference range.
+    ///
+    /// Fields and mismatches missing from the reference statistics are counted as zero in all
+    /// reference runs.
+    pub fn outliers(&self, summary: &Summary) -> Vec<Outlier> {
+        let zero = Stats::new(vec![0; self.sample_size]);
+        let mut outliers: Vec<_> = [
+            (
+                "upstream unstable",
+                &self.upstream_unstable,
+                summary.upstream_unstable,
+            ),
+            (
+                "not reproducible",
+                &self.not_reproducible,
+                summary.not_reproducible,
+            ),
+            (
+                "usable answers",
+                &self.usable_answers,
+                summary.usable_answers,
+            ),
+            (
+                "target disagrees",
+                &self.target_disagreements,
+                summary.total(),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(name, stats, count)| Outlier::check(name.to_string(), stats, count))
+        .collect();
+
+        let fields: BTreeSet<Field> = self
+            .fields
+            .keys()
+            .chain(summary.fields().keys())
+            .copied()
+            .collect();
+        for field in fields {
+            let fstats = self.fields.get(&field);
+            let total = fstats.map_or(&zero, |fstats| &fstats.total);
+            outliers.extend(Outlier::check(
+                field.to_string(),
+                total,
+                summary.field_count(field),
+            ));
+
+            let mut categories: BTreeSet<(&str, &str)> = fstats
+                .iter()
+                .flat_map(|fstats| fstats.mismatches.iter())
+                .map(|mstats| (mstats.exp_val.as_str(), mstats.got_val.as_str()))
+                .collect();
+            if let Some(fdis) = summary.fields().get(&field) {
+                categories.extend(
+                    fdis.mismatches()
+                        .iter()
+                        .map(|m| (m.exp_val.as_str(), m.got_val.as_str())),
+                );
+            }
+            for (exp_val, got_val) in categories {
+                let stats = fstats
+                    .and_then(|fstats| {
+                        fstats
+                            .mismatches
+                            .iter()
+                            .find(|m| m.exp_val == exp_val && m.got_val == got_val)
+                    })
+                    .map_or(&zero, |mstats| &mstats.count);
+                let cat = Category {
+                    field,
+                    exp_val: exp_val.to_string(),
+                    got_val: got_val.to_string(),
+                };
+                outliers.extend(Outlier::check(
+                    format!("{} {} != {}", field, exp_val, got_val),
+                    stats,
+                    mismatch_count(summary, &cat),
+                ));
+            }
+        }
+        outliers
+    }
+}
+
+/// Return the number of queries in the summary with the given mismatch.
+fn mismatch_count(summary: &Summary, cat: &Category) -> u64 {
+    summary
+        .fields()
+        .get(&cat.field)
+        .and_then(|fdis| {
+            fdis.mismatches()
+                .iter()
+                .find(|m| m.exp_val == cat.exp_val && m.got_val == cat.got_val)
+        })
+        .map_or(0, |mmqueries| mmqueries.queries.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FieldWeight;
+    use crate::dataformat::Report;
+    use crate::matcher::{FieldMismatches, Mismatch};
+    use crate::QKey;
+    use domain::base::iana::rcode::Rcode;
+
+    fn summary(rcodes: &[(Rcode, Rcode, &[QKey])]) -> Summary {
+        let mut fmismatches = FieldMismatches::new();
+        for (exp, got, queries) in rcodes {
+            fmismatches.insert(
+                Mismatch::Rcode(*exp, *got),
+                queries.iter().copied().collect(),
+            );
+        }
+        let mut report = Report::new();
+        report.total_answers = 100;
+        report.set_target_disagrees([(Field::Rcode, fmismatches)].into_iter().collect());
+        Summary::from_report(&report, &[FieldWeight::Rcode], None)
+    }
+
+    #[test]
+    fn stats() {
+        let stats = Stats::new(vec![4, 1, 3, 2]);
+        assert_eq!(stats.mean, 2.5);
+        assert!((stats.stdev - 1.2909944).abs() < 1e-6);
+        assert_eq!((stats.min, stats.max), (1, 4));
+        assert_eq!(stats.median, 2.5);
+        assert_eq!(stats.p25, 1.75);
+        assert_eq!(stats.percentile_rank(2), 0.5);
+        assert!(!stats.is_outlier(4));
+        assert!(stats.is_outlier(5));
+
+        let empty = Stats::new(vec![]);
+        assert_eq!((empty.mean, empty.stdev, empty.median), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn summary_statistics() {
+        use Rcode::*;
+
+        let summaries = [
+            summary(&[(NoError, ServFail, &[1, 2, 3]), (NXDomain, NoError, &[4])]),
+            summary(&[(NoError, ServFail, &[1])]),
+        ];
+        let stats = SummaryStatistics::new(&summaries);
+        assert_eq!(stats.sample_size, 2);
+        assert_eq!(stats.target_disagreements.samples, vec![4, 1]);
+        assert_eq!(stats.usable_answers.samples, vec![100, 100]);
+
+        let rcode = &stats.fields[&Field::Rcode];
+        assert_eq!(rcode.total.samples, vec![4, 1]);
+        assert_eq!(rcode.mismatches.len(), 2);
+        assert_eq!(rcode.mismatches[0].exp_val, "NOERROR");
+        assert_eq!(rcode.mismatches[0].count.samples, vec![3, 1]);
+        assert_eq!(rcode.mismatches[1].exp_val, "NXDOMAIN");
+        assert_eq!(rcode.mismatches[1].count.samples, vec![1, 0]);
+
+        let ser = serde_json::to_string(&stats).unwrap();
+        let deser = serde_json::from_str::<SummaryStatistics>(&ser).unwrap();
+        assert_eq!(
+            deser.fields[&Field::Rcode].mismatches[0].count.samples,
+            vec![3, 1]
+        );
+        assert!((deser.target_disagreements.stdev - stats.target_disagreements.stdev).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outliers() {
+        use Rcode::*;
+
+        let summaries = [
+            summary(&[(NoError, ServFail, &[1, 2, 3])]),
+            summary(&[(NoError, ServFail, &[1])]),
+        ];
+        let stats = SummaryStatistics::new(&summaries);
+        assert!(stats.outliers(&summaries[0]).is_empty());
+
+        let outliers = stats.outliers(&summary(&[
+            (NoError, ServFail, &[1, 2]),
+            (NXDomain, NoError, &[4, 5, 6, 7]),
+        ]));
+        let names: Vec<_> = outliers.iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["target disagrees", "rcode", "rcode NXDOMAIN != NOERROR"]
+        );
+        assert_eq!(outliers[0].count, 6);
+        assert_eq!((outliers[0].min, outliers[0].max), (1, 3));
+        assert_eq!(outliers[0].percentile_rank, 1.0);
+        assert_eq!((outliers[2].min, outliers[2].max), (0, 0));
+    }
+}
@@ -1,6 +1,10 @@
 // TODO document all pub
 
-use crate::{config::ServerConfig, database::queriesdb::Query, QKey};
+use crate::{
+    config::{ServerConfig, TransportProtocol},
+    database::queriesdb::Query,
+    DnsReply, FailureKind, QKey, ResponseMeta, ServerResponse, ServerResponseList,
+};
 /// Module for asynchronously transmitting queries.
 use async_std::{
     io,
@@ -8,11 +12,11 @@ use async_std::{
     prelude::*,
     task,
 };
+use domain::base::Message;
 use futures::channel::mpsc;
 use futures::sink::SinkExt;
 use futures::stream::FuturesUnordered;
-use std::mem;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime};
 
 pub type Sender<T> = mpsc::UnboundedSender<T>;
 pub type Receiver<T> = mpsc::UnboundedReceiver<T>;
@@ -60,18 +64,52 @@ impl Eq for RawResponse {}
 pub struct RawResponseList {
     pub key: QKey,
     pub responses: Vec<RawResponse>,
+    /// Details about each response, if known.
+    pub meta: Vec<ResponseMeta>,
 }
 
-async fn transmit_query(
-    query: Query,
-    addrs: Vec<SocketAddr>,
-    mut sink: Sender<RawResponseList>,
+impl From<RawResponse> for ServerResponse {
+    fn from(value: RawResponse) -> Self {
+        match value {
+            RawResponse::Timeout => ServerResponse::Timeout,
+            RawResponse::Data { delay, wire } => match Message::from_octets(wire) {
+                Ok(message) => ServerResponse::Data(DnsReply { delay, message }),
+                Err(_) => ServerResponse::Malformed,
+            },
+        }
+    }
+}
+
+impl From<RawResponseList> for ServerResponseList {
+    fn from(value: RawResponseList) -> Self {
+        ServerResponseList {
+            key: value.key,
+            replies: value.responses.into_iter().map(|r| r.into()).collect(),
+            meta: value.meta,
+        }
+    }
+}
+
+/// Send a query to all servers and wait for their responses.
+///
+/// Responses are returned in the same order as the server addresses. A server which doesn't
+/// respond within the timeout is recorded as `RawResponse::Timeout`, and the reason is recorded
+/// in its `ResponseMeta`.
+///
+/// Queries are sent once over UDP, so the recorded transport is always UDP, with no retries and
+/// no TCP fallback.
+pub async fn query_servers(
+    query: &Query,
+    addrs: &[SocketAddr],
     timeout: Duration,
-) -> Result<()> {
+) -> RawResponseList {
     let mut futures = FuturesUnordered::new();
 
     for (i, addr) in addrs.iter().enumerate() {
         let qwire = query.wire.clone();
+        let sent_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
         let reply = io::timeout(timeout, async move {
             let bindaddr = match addr {
                 SocketAddr::V4(..) => "0.0.0.0:0",
@@ -83,29 +121,47 @@ async fn transmit_query(
             let since = Instant::now();
             let mut buf = vec![0; 64 * 1024];
             let n = socket.recv(&mut buf).await?;
-            Ok((
-                i,
-                RawResponse::Data {
-                    delay: since.elapsed(),
-                    wire: buf[0..n].to_vec(),
-                },
-            ))
+            Ok(RawResponse::Data {
+                delay: since.elapsed(),
+                wire: buf[0..n].to_vec(),
+            })
+        });
+        futures.push(async move {
+            let meta = ResponseMeta {
+                transport: TransportProtocol::Udp,
+                sent_at,
+                retries: 0,
+                tcp_fallback: false,
+                failure: None,
+            };
+            (i, meta, reply.await)
         });
-        futures.push(reply);
     }
 
     let mut responses = vec![RawResponse::Timeout; addrs.len()];
-    while let Some(res) = futures.next().await {
-        if let Ok((i, reply)) = res {
-            let _ = mem::replace(&mut responses[i], reply);
+    let mut meta = vec![ResponseMeta::default(); addrs.len()];
+    while let Some((i, mut rmeta, res)) = futures.next().await {
+        match res {
+            Ok(reply) => responses[i] = reply,
+            Err(e) => rmeta.failure = Some(FailureKind::from(&e)),
         }
+        meta[i] = rmeta;
     }
-
-    sink.send(RawResponseList {
+    RawResponseList {
         key: query.key,
         responses,
-    })
-    .await?;
+        meta,
+    }
+}
+
+async fn transmit_query(
+    query: Query,
+    addrs: Vec<SocketAddr>,
+    mut sink: Sender<RawResponseList>,
+    timeout: Duration,
+) -> Result<()> {
+    let responses = query_servers(&query, &addrs, timeout).await;
+    sink.send(responses).await?;
     Ok(())
 }
 
@@ -150,23 +206,24 @@ mod tests {
             .await
             .is_ok());
             drop(sender);
+            let list = receiver.next().await.unwrap();
+            assert_eq!(list.key, query.key);
             assert_eq!(
-                receiver.next().await,
-                Some(RawResponseList {
-                    key: query.key,
-                    responses: vec![RawResponse::Data {
-                        delay: Duration::from_secs(0),
-                        wire: query.wire.clone(),
-                    }],
-                })
-            );
-            assert_eq!(
-                receiver.next().await,
-                Some(RawResponseList {
-                    key: query.key,
-                    responses: vec![RawResponse::Timeout]
-                })
+                list.responses,
+                vec![RawResponse::Data {
+                    delay: Duration::from_secs(0),
+                    wire: query.wire.clone(),
+                }]
             );
+            assert_eq!(list.meta[0].failure, None);
+            assert!(list.meta[0].sent_at > 0);
+            let list = receiver.next().await.unwrap();
+            assert_eq!(list.responses, vec![RawResponse::Timeout]);
+            // echo socket may be already closed, resulting in ICMP port unreachable
+            assert!(matches!(
+                list.meta[0].failure,
+                Some(FailureKind::Timeout | FailureKind::Refused)
+            ));
             assert_eq!(receiver.next().await, None);
         });
         let echo = task::spawn(udp_echo_once(socket));
@@ -174,7 +231,7 @@ mod tests {
         let mut futures = FuturesUnordered::new();
         futures.push(transmission);
         futures.push(echo);
-        while let Some(_) = futures.next().await {}
+        while futures.next().await.is_some() {}
 
         Ok(())
     }
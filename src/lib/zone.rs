@@ -0,0 +1,424 @@
+use crate::{error::Error, qprep::QueryOptions};
+use domain::base::{iana::Rtype, name::Dname};
+use log::warn;
+use rand::{distributions::Alphanumeric, Rng};
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Length of random labels in generated names.
+const RANDOM_LABEL_LEN: usize = 12;
+
+type Name = Dname<Vec<u8>>;
+
+/// Owner names and types present in a master-format zone file.
+#[derive(Debug, Clone, Default)]
+pub struct Zone {
+    /// Unique owner name and type pairs, in the order of the zone file.
+    pub records: Vec<(Name, Rtype)>,
+    /// Owner of the SOA record, or the first origin if the zone has no SOA.
+    pub apex: Option<Name>,
+}
+
+/// Logical entry of a zone file, which may span multiple lines in parentheses.
+struct Entry {
+    line: usize,
+    /// Entry starts with whitespace, so the owner is the previous one.
+    blank_owner: bool,
+    tokens: Vec<String>,
+}
+
+/// Split zone file into entries, dropping comments and parentheses.
+fn entries(text: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    let mut current: Option<Entry> = None;
+    let mut depth = 0;
+    for (i, line) in text.lines().enumerate() {
+        let entry = current.get_or_insert_with(|| Entry {
+            line: i + 1,
+            blank_owner: line.starts_with(char::is_whitespace),
+            tokens: Vec::new(),
+        });
+        let mut token: Option<String> = None;
+        let mut chars = line.chars();
+        let mut quoted = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    let token = token.get_or_insert_with(String::new);
+                    token.push(c);
+                    token.extend(chars.next());
+                }
+                '"' => {
+                    quoted = !quoted;
+                    token.get_or_insert_with(String::new).push(c);
+                }
+                _ if quoted => token.get_or_insert_with(String::new).push(c),
+                ';' => break,
+                '(' | ')' => {
+                    entry.tokens.extend(token.take());
+                    depth += if c == '(' { 1 } else { -1 };
+                    if depth < 0 {
+                        return Err(zone_error(i + 1, "unbalanced parentheses"));
+                    }
+                }
+                _ if c.is_whitespace() => entry.tokens.extend(token.take()),
+                _ => token.get_or_insert_with(String::new).push(c),
+            }
+        }
+        if quoted {
+            return Err(zone_error(i + 1, "unterminated string"));
+        }
+        entry.tokens.extend(token);
+        if depth == 0 {
+            entries.extend(current.take());
+        }
+    }
+    if depth != 0 {
+        return Err(zone_error(text.lines().count(), "unbalanced parentheses"));
+    }
+    Ok(entries)
+}
+
+fn zone_error(line: usize, msg: &str) -> Error {
+    Error::InvalidZone(format!("line {}: {}", line, msg))
+}
+
+/// Resolve a name from a zone file relative to the origin.
+fn resolve(token: &str, origin: &Option<Name>, line: usize) -> Result<Name, Error> {
+    let name = match (token, origin) {
+        ("@", Some(origin)) => return Ok(origin.clone()),
+        (_, _) if token.ends_with('.') && !token.ends_with("\\.") => token.to_string(),
+        (_, Some(origin)) if origin.is_root() => format!("{}.", token),
+        (_, Some(origin)) => format!("{}.{}.", token, origin),
+        (_, None) => return Err(zone_error(line, "relative name without origin")),
+    };
+    Name::from_str(&name).map_err(|e| zone_error(line, &format!("invalid name {}: {}", token, e)))
+}
+
+/// Return a child of the name with the given label, or `None` if it would be too long.
+fn child(label: &str, parent: &Name) -> Option<Name> {
+    let name = if parent.is_root() {
+        format!("{}.", label)
+    } else {
+        format!("{}.{}.", label, parent)
+    };
+    Name::from_str(&name).ok()
+}
+
+fn is_ttl(token: &str) -> bool {
+    token.starts_with(|c: char| c.is_ascii_digit())
+}
+
+fn is_class(token: &str) -> bool {
+    let token = token.to_ascii_uppercase();
+    matches!(token.as_str(), "IN" | "CH" | "CS" | "HS")
+        || token
+            .strip_prefix("CLASS")
+            .is_some_and(|n| n.parse::<u16>().is_ok())
+}
+
+impl Zone {
+    /// Parse owner names and types from a master-format zone file.
+    ///
+    /// Relative names require either the origin or an ``$ORIGIN`` directive. Record data isn't
+    /// validated, and ``$INCLUDE`` and ``$GENERATE`` aren't supported.
+    pub fn parse(text: &str, origin: Option<Name>) -> Result<Zone, Error> {
+        let mut first_origin = origin.clone();
+        let mut origin = origin;
+        let mut owner: Option<Name> = None;
+        let mut seen = HashSet::new();
+        let mut zone = Zone::default();
+
+        for entry in entries(text)? {
+            let line = entry.line;
+            let mut tokens = entry.tokens.iter().map(String::as_str);
+            let first = if entry.blank_owner {
+                None
+            } else {
+                match tokens.next() {
+                    Some(token) => Some(token),
+                    None => continue,
+                }
+            };
+            match first {
+                Some(directive) if directive.starts_with('$') => {
+                    match directive.to_ascii_uppercase().as_str() {
+                        "$ORIGIN" => {
+                            let name = tokens
+                                .next()
+                                .ok_or_else(|| zone_error(line, "$ORIGIN without name"))?;
+                            origin = Some(resolve(name, &origin, line)?);
+                            first_origin = first_origin.or_else(|| origin.clone());
+                        }
+                        "$TTL" => {}
+                        _ => {
+                            return Err(zone_error(
+                                line,
+                                &format!("unsupported directive {}", directive),
+                            ))
+                        }
+                    }
+                    continue;
+                }
+                Some(name) => owner = Some(resolve(name, &origin, line)?),
+                None if entry.tokens.is_empty() => continue,
+                None => {}
+            }
+            let owner = owner
+                .clone()
+                .ok_or_else(|| zone_error(line, "record without owner"))?;
+
+            let rtype = tokens
+                .find(|token| !is_ttl(token) && !is_class(token))
+                .ok_or_else(|| zone_error(line, "record without type"))?;
+            let rtype = Rtype::from_str(&rtype.to_ascii_uppercase())
+                .map_err(|_| zone_error(line, &format!("invalid type {}", rtype)))?;
+            if rtype == Rtype::Soa && zone.apex.is_none() {
+                zone.apex = Some(owner.clone());
+            }
+            if seen.insert((owner.to_string().to_ascii_lowercase(), rtype)) {
+                zone.records.push((owner, rtype));
+            }
+        }
+        if zone.apex.is_none() {
+            zone.apex = first_origin;
+        }
+        Ok(zone)
+    }
+
+    /// Generate queries for all owner names and types in the zone, followed by `wildcard` queries
+    /// for random names matching each wildcard record and `nxdomain` queries for random
+    /// nonexistent names.
+    ///
+    /// Fails if nonexistent names are requested, but the zone has no names they could be
+    /// generated below.
+    pub fn queries<R: Rng>(
+        &self,
+        options: &QueryOptions,
+        rng: &mut R,
+        wildcard: usize,
+        nxdomain: usize,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let owners: HashSet<String> = self
+            .records
+            .iter()
+            .map(|(name, _)| name.to_string().to_ascii_lowercase())
+            .collect();
+        let random_name = |rng: &mut R, parent: &Name| loop {
+            let label: String = (0..RANDOM_LABEL_LEN)
+                .map(|_| rng.sample(Alphanumeric) as char)
+                .collect();
+            let name = child(&label.to_ascii_lowercase(), parent)?;
+            if !owners.contains(&name.to_string()) {
+                return Some(name);
+            }
+        };
+
+        let mut queries: Vec<_> = self
+            .records
+            .iter()
+            .map(|(name, rtype)| options.build_query(name, *rtype))
+            .collect();
+
+        // name with the leading wildcard label removed
+        let wildcard_parent = |name: &Name| match name.to_string().strip_prefix("*.") {
+            Some(parent) => Name::from_str(parent).expect("valid parent of a valid name"),
+            None => Name::root_vec(),
+        };
+        let wildcards: Vec<_> = self
+            .records
+            .iter()
+            .filter(|(name, _)| name.first().is_wildcard())
+            .collect();
+        for (name, rtype) in &wildcards {
+            let parent = wildcard_parent(name);
+            for _ in 0..wildcard {
+                match random_name(rng, &parent) {
+                    Some(qname) => queries.push(options.build_query(qname, *rtype)),
+                    None => {
+                        warn!("skipping wildcard queries for {}: name too long", name);
+                        break;
+                    }
+                }
+            }
+        }
+
+        // names below wildcards, delegations or DNAMEs may exist, so they aren't used as parents
+        let cuts: Vec<_> = self
+            .records
+            .iter()
+            .filter(|(name, rtype)| match *rtype {
+                Rtype::Ns => Some(name) != self.apex.as_ref(),
+                Rtype::Dname => true,
+                _ => false,
+            })
+            .map(|(name, _)| name)
+            .collect();
+        let mut unique = HashSet::new();
+        let parents: Vec<_> = self
+            .records
+            .iter()
+            .map(|(name, _)| name)
+            .filter(|name| !name.first().is_wildcard())
+            .filter(|name| !wildcards.iter().any(|(w, _)| wildcard_parent(w) == **name))
+            .filter(|name| !cuts.iter().any(|cut| name.ends_with(*cut)))
+            .filter(|name| unique.insert(name.to_string().to_ascii_lowercase()))
+            .collect();
+        if nxdomain > 0 && parents.is_empty() {
+            return Err(Error::InvalidZone(
+                "no names to generate nonexistent names below".to_string(),
+            ));
+        }
+        for _ in 0..nxdomain {
+            let parent = parents[rng.gen_range(0..parents.len())];
+            match random_name(rng, parent) {
+                Some(qname) => queries.push(options.build_query(qname, Rtype::A)),
+                None => warn!("skipping nonexistent name below {}: name too long", parent),
+            }
+        }
+        Ok(queries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use domain::base::Message;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    const ZONE: &str = "$ORIGIN example.com.
+$TTL 3600
+@   IN  SOA ns1 hostmaster (
+            2026101801 ; serial
+            7200 3600 1209600 3600 )
+    IN  NS  ns1
+    NS  ns2.example.net.
+ns1 300 IN A 192.0.2.1
+ns1     AAAA 2001:db8::1
+www IN 60 CNAME ns1 ; alias
+txt TXT \"a ; b\" \"(\"
+*.wild A 192.0.2.2
+sub NS ns1.sub
+ns1.sub A 192.0.2.3
+";
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn question(wire: &[u8]) -> (String, Rtype) {
+        let msg = Message::from_octets(wire).unwrap();
+        let q = msg.sole_question().unwrap();
+        (q.qname().to_string(), q.qtype())
+    }
+
+    #[test]
+    fn parse() {
+        let zone = Zone::parse(ZONE, None).unwrap();
+        assert_eq!(zone.apex, Some(name("example.com.")));
+        let records: Vec<_> = zone
+            .records
+            .iter()
+            .map(|(name, rtype)| (name.to_string(), *rtype))
+            .collect();
+        let expected = [
+            ("example.com", Rtype::Soa),
+            ("example.com", Rtype::Ns),
+            ("ns1.example.com", Rtype::A),
+            ("ns1.example.com", Rtype::Aaaa),
+            ("www.example.com", Rtype::Cname),
+            ("txt.example.com", Rtype::Txt),
+            ("*.wild.example.com", Rtype::A),
+            ("sub.example.com", Rtype::Ns),
+            ("ns1.sub.example.com", Rtype::A),
+        ];
+        assert_eq!(
+            records,
+            expected
+                .iter()
+                .map(|(n, t)| (n.to_string(), *t))
+                .collect::<Vec<_>>()
+        );
+
+        let zone = Zone::parse("www A 192.0.2.1", Some(name("example.org."))).unwrap();
+        assert_eq!(zone.records, vec![(name("www.example.org."), Rtype::A)]);
+
+        assert!(Zone::parse("www A 192.0.2.1", None).is_err());
+        assert!(Zone::parse("$INCLUDE other.zone", None).is_err());
+        assert!(Zone::parse("example.com. SOA ( a b", None).is_err());
+        assert!(Zone::parse("example.com. 3600 IN", None).is_err());
+    }
+
+    #[test]
+    fn queries() {
+        let zone = Zone::parse(ZONE, None).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let queries = zone
+            .queries(&QueryOptions::default(), &mut rng, 2, 5)
+            .unwrap();
+        assert_eq!(queries.len(), zone.records.len() + 2 + 5);
+        assert_eq!(
+            question(&queries[0]),
+            ("example.com".to_string(), Rtype::Soa)
+        );
+
+        let extra: Vec<_> = queries[zone.records.len()..]
+            .iter()
+            .map(|wire| question(wire))
+            .collect();
+        for (qname, rtype) in &extra[..2] {
+            assert!(qname.ends_with(".wild.example.com"));
+            assert_eq!(qname.split('.').next().unwrap().len(), RANDOM_LABEL_LEN);
+            assert_eq!(*rtype, Rtype::A);
+        }
+        for (qname, _) in &extra[2..] {
+            assert!(!qname.ends_with(".wild.example.com"));
+            assert!(!qname.ends_with("sub.example.com"));
+            assert!(qname.ends_with("example.com"));
+        }
+    }
+
+    #[test]
+    fn nxdomain_parents() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let options = QueryOptions::default();
+
+        // without SOA, the origin is the apex and its NS records aren't a delegation
+        let zone = Zone::parse(
+            "$ORIGIN example.com.\n@ NS ns1\nns1 A 192.0.2.1\n$ORIGIN example.net.\n",
+            None,
+        )
+        .unwrap();
+        assert_eq!(zone.apex, Some(name("example.com.")));
+        let queries = zone.queries(&options, &mut rng, 0, 3).unwrap();
+        assert_eq!(queries.len(), zone.records.len() + 3);
+
+        // names at and below DNAME owners are redirected
+        let zone = Zone::parse(
+            "@ NS ns1\nns1 A 192.0.2.1\nold DNAME new.example.\nx.old A 192.0.2.2\n",
+            Some(name("example.org.")),
+        )
+        .unwrap();
+        let queries = zone.queries(&options, &mut rng, 0, 20).unwrap();
+        for wire in &queries[zone.records.len()..] {
+            assert!(!question(wire).0.ends_with("old.example.org"));
+        }
+
+        let zone = Zone::parse("sub NS ns1.sub", Some(name("example.org."))).unwrap();
+        assert!(zone.queries(&options, &mut rng, 0, 0).is_ok());
+        assert!(zone.queries(&options, &mut rng, 0, 1).is_err());
+    }
+
+    #[test]
+    fn queries_long_name() {
+        // 244 octets, so no random label fits below them
+        let long = |last: &str| format!("{0}.{0}.{0}.{1}.", "a".repeat(63), last.repeat(50));
+        let input = format!("*.{} A 192.0.2.1\n{} A 192.0.2.2", long("b"), long("c"));
+        let zone = Zone::parse(&input, None).unwrap();
+        let mut rng = StdRng::seed_from_u64(0);
+        let queries = zone
+            .queries(&QueryOptions::default(), &mut rng, 2, 5)
+            .unwrap();
+        assert_eq!(queries.len(), zone.records.len());
+    }
+}